@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::Instant;
+
+struct Entry {
+    addrs: Vec<SocketAddr>,
+    expires: Instant,
+}
+
+/// A cache of resolved addresses, shared between clones of a connector.
+pub struct DnsCache {
+    entries: Mutex<HashMap<(String, u16), Entry>>,
+}
+
+impl DnsCache {
+    pub fn new() -> DnsCache {
+        DnsCache { entries: Mutex::new(HashMap::new()) }
+    }
+
+    pub fn get(&self, host: &str, port: u16) -> Option<Vec<SocketAddr>> {
+        let entries = self.entries.lock().unwrap();
+        match entries.get(&key(host, port)) {
+            Some(entry) if entry.expires > Instant::now() => Some(entry.addrs.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn insert(&self, host: &str, port: u16, addrs: Vec<SocketAddr>, expires: Instant) {
+        let mut entries = self.entries.lock().unwrap();
+        let now = Instant::now();
+        entries.retain(|_, e| e.expires > now);
+        entries.insert(key(host, port), Entry { addrs, expires });
+    }
+
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
+
+// hostnames are case insensitive, so entries are shared between spellings
+fn key(host: &str, port: u16) -> (String, u16) {
+    (host.to_ascii_lowercase(), port)
+}
+
+/// The address most recently connected to for each host, shared between
+/// clones of a connector.
+pub struct StickyAddrs {
+    addrs: Mutex<HashMap<(String, u16), SocketAddr>>,
+}
+
+impl StickyAddrs {
+    pub fn new() -> StickyAddrs {
+        StickyAddrs { addrs: Mutex::new(HashMap::new()) }
+    }
+
+    pub fn get(&self, host: &str, port: u16) -> Option<SocketAddr> {
+        self.addrs.lock().unwrap().get(&key(host, port)).cloned()
+    }
+
+    pub fn insert(&self, host: &str, port: u16, addr: SocketAddr) {
+        self.addrs.lock().unwrap().insert(key(host, port), addr);
+    }
+
+    pub fn remove(&self, host: &str, port: u16) {
+        self.addrs.lock().unwrap().remove(&key(host, port));
+    }
+
+    pub fn clear(&self) {
+        self.addrs.lock().unwrap().clear();
+    }
+}
+
+/// The addresses which recently failed, and when their cooldown ends, shared
+/// between clones of a connector.
+pub struct FailedAddrs {
+    addrs: Mutex<HashMap<SocketAddr, Instant>>,
+}
+
+impl FailedAddrs {
+    pub fn new() -> FailedAddrs {
+        FailedAddrs { addrs: Mutex::new(HashMap::new()) }
+    }
+
+    pub fn is_cooling_down(&self, addr: &SocketAddr) -> bool {
+        match self.addrs.lock().unwrap().get(addr) {
+            Some(&until) => until > Instant::now(),
+            None => false,
+        }
+    }
+
+    pub fn insert(&self, addr: SocketAddr, until: Instant) {
+        let mut addrs = self.addrs.lock().unwrap();
+        let now = Instant::now();
+        addrs.retain(|_, until| *until > now);
+        addrs.insert(addr, until);
+    }
+
+    pub fn remove(&self, addr: &SocketAddr) {
+        self.addrs.lock().unwrap().remove(addr);
+    }
+
+    pub fn clear(&self) {
+        self.addrs.lock().unwrap().clear();
+    }
+}
@@ -0,0 +1,260 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+/// The time before a deadline at which a timeout is attributed to it.
+///
+/// Timeouts have millisecond granularity on most platforms, so an attempt
+/// bounded by the deadline may time out slightly before it.
+const DEADLINE_SLACK_MS: u64 = 10;
+
+/// An error establishing a connection.
+///
+/// The errors returned by `HttpTimeoutConnector` are `hyper::Error::Io`
+/// values with the same `io::ErrorKind` as the underlying failure, wrapping a
+/// `ConnectError` which can be used to determine the cause of the failure.
+///
+/// # Examples
+///
+/// ```
+/// extern crate hyper;
+/// extern crate hyper_timeout_connector;
+///
+/// use hyper_timeout_connector::ConnectError;
+///
+/// fn is_timeout(err: &hyper::Error) -> bool {
+///     match *err {
+///         hyper::Error::Io(ref e) => {
+///             match e.get_ref().and_then(|e| e.downcast_ref::<ConnectError>()) {
+///                 Some(&ConnectError::Timeout(_)) => true,
+///                 _ => false,
+///             }
+///         }
+///         _ => false,
+///     }
+/// }
+/// # fn main() {}
+/// ```
+#[derive(Debug)]
+pub enum ConnectError {
+    /// A connection attempt timed out.
+    Timeout(io::Error),
+    /// The total timeout, or the deadline passed to
+    /// `HttpTimeoutConnector::connect_with_deadline`, elapsed before a
+    /// connection was established.
+    ///
+    /// This is distinct from `Timeout`, which indicates that an attempt was
+    /// cut short by the connection timeout while time remained to try other
+    /// addresses or retry.
+    DeadlineExceeded(io::Error),
+    /// The hostname could not be resolved, or resolution timed out.
+    Resolve(io::Error),
+    /// The hostname was resolved, but there were no addresses to connect to.
+    ///
+    /// This happens if the host resolved to an empty list of addresses, if
+    /// none of its addresses remained after applying the address family
+    /// configuration, or if the address filter rejected all of them. The
+    /// error message includes the hostname.
+    NoAddresses(io::Error),
+    /// Every address of the host was private, and connections to private
+    /// addresses are blocked.
+    ///
+    /// This is only returned if
+    /// `HttpTimeoutConnector::set_block_private_addresses` is enabled. The
+    /// error has kind `PermissionDenied`, and its message includes the
+    /// hostname.
+    BlockedAddress(io::Error),
+    /// The remote host refused the connection.
+    Refused(io::Error),
+    /// A socket could not be created because the process or system has run
+    /// out of file descriptors.
+    ///
+    /// This usually means the open file limit (`ulimit -n`) needs to be
+    /// raised, or that connections are being leaked. Connect calls stop
+    /// trying further addresses and do not retry after this error, since
+    /// doing so would only fail the same way.
+    ResourceExhausted(io::Error),
+    /// The connection to the proxy was established, but the proxy failed to
+    /// establish a tunnel to the remote host.
+    Proxy(io::Error),
+    /// Some other error occurred.
+    Other(io::Error),
+}
+
+impl ConnectError {
+    /// Classifies an error returned by a connection attempt.
+    pub(crate) fn from_connect(err: io::Error) -> ConnectError {
+        if is_fd_exhausted(&err) {
+            return ConnectError::ResourceExhausted(err);
+        }
+
+        match err.kind() {
+            io::ErrorKind::TimedOut => ConnectError::Timeout(err),
+            io::ErrorKind::ConnectionRefused => ConnectError::Refused(err),
+            _ => ConnectError::Other(err),
+        }
+    }
+
+    /// Determines if the error means that no address of the host could be
+    /// connected to, as opposed to the connect call being rejected or running
+    /// out of time.
+    pub(crate) fn is_unreachable(&self) -> bool {
+        match *self {
+            ConnectError::Timeout(_) |
+            ConnectError::Resolve(_) |
+            ConnectError::Refused(_) => true,
+            // addresses rejected by the address filter are a policy decision
+            ConnectError::NoAddresses(ref e) => e.kind() != io::ErrorKind::PermissionDenied,
+            ConnectError::Other(ref e) => e.get_ref().map_or(false, |e| e.is::<AttemptErrors>()),
+            _ => false,
+        }
+    }
+
+    /// Reports a timeout which happened because the deadline passed as such.
+    pub(crate) fn check_deadline(self, deadline: Option<Instant>) -> ConnectError {
+        let expired = match deadline {
+            Some(deadline) => Instant::now() + Duration::from_millis(DEADLINE_SLACK_MS) >= deadline,
+            None => false,
+        };
+
+        match self {
+            ConnectError::Timeout(ref e) |
+            ConnectError::Resolve(ref e) if expired && e.kind() == io::ErrorKind::TimedOut => {
+                let err = io::Error::new(io::ErrorKind::TimedOut,
+                                         format!("overall connect deadline exceeded: {}", e));
+                ConnectError::DeadlineExceeded(err)
+            }
+            err => err,
+        }
+    }
+
+    /// Creates the error for a host with no addresses to connect to.
+    pub(crate) fn no_addresses(host: &str) -> ConnectError {
+        let err = io::Error::new(io::ErrorKind::InvalidInput,
+                                 format!("no addresses to connect to for host `{}`", host));
+        ConnectError::NoAddresses(err)
+    }
+
+    /// Returns the underlying IO error.
+    pub fn io_error(&self) -> &io::Error {
+        match *self {
+            ConnectError::Timeout(ref e) |
+            ConnectError::DeadlineExceeded(ref e) |
+            ConnectError::Resolve(ref e) |
+            ConnectError::NoAddresses(ref e) |
+            ConnectError::BlockedAddress(ref e) |
+            ConnectError::Refused(ref e) |
+            ConnectError::ResourceExhausted(ref e) |
+            ConnectError::Proxy(ref e) |
+            ConnectError::Other(ref e) => e,
+        }
+    }
+}
+
+impl fmt::Display for ConnectError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConnectError::Resolve(ref e) => write!(fmt, "error resolving host: {}", e),
+            ConnectError::Proxy(ref e) => write!(fmt, "error establishing proxy tunnel: {}", e),
+            ConnectError::ResourceExhausted(ref e) => {
+                write!(fmt, "out of file descriptors, check the open file limit: {}", e)
+            }
+            _ => fmt::Display::fmt(self.io_error(), fmt),
+        }
+    }
+}
+
+impl Error for ConnectError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(self.io_error())
+    }
+}
+
+impl From<ConnectError> for io::Error {
+    fn from(err: ConnectError) -> io::Error {
+        io::Error::new(err.io_error().kind(), err)
+    }
+}
+
+/// The errors encountered while trying each of the addresses associated with
+/// a host.
+///
+/// When every connection attempt fails, the underlying IO error of the
+/// returned `ConnectError` wraps an `AttemptErrors` and has the same
+/// `io::ErrorKind` as the last failure.
+#[derive(Debug)]
+pub struct AttemptErrors(Vec<(SocketAddr, io::Error)>);
+
+impl AttemptErrors {
+    /// Returns the addresses that were tried, in order, along with the error
+    /// encountered for each.
+    pub fn errors(&self) -> &[(SocketAddr, io::Error)] {
+        &self.0
+    }
+}
+
+impl fmt::Display for AttemptErrors {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        if let [(addr, err)] = &self.0[..] {
+            return write!(fmt, "failed to connect to {}: {}", addr, err);
+        }
+
+        fmt.write_str("failed to connect to any address (")?;
+        for (i, (addr, err)) in self.0.iter().enumerate() {
+            if i > 0 {
+                fmt.write_str("; ")?;
+            }
+            write!(fmt, "{}: {}", addr, err)?;
+        }
+        fmt.write_str(")")
+    }
+}
+
+impl Error for AttemptErrors {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.0.last().map(|(_, e)| e as &(dyn Error + 'static))
+    }
+}
+
+/// Combines the errors from each connection attempt into a single error.
+pub(crate) fn attempts_failed(errors: Vec<(SocketAddr, io::Error)>) -> io::Error {
+    match errors.last().map(|(_, e)| e.kind()) {
+        Some(kind) => io::Error::new(kind, AttemptErrors(errors)),
+        None => {
+            io::Error::new(io::ErrorKind::InvalidInput,
+                           "could not resolve to any addresses")
+        }
+    }
+}
+
+/// Determines if an error was caused by running out of file descriptors.
+///
+/// Combined attempt errors are classified by the last attempt's error.
+pub(crate) fn is_fd_exhausted(err: &io::Error) -> bool {
+    if let Some(code) = err.raw_os_error() {
+        return is_fd_exhausted_code(code);
+    }
+
+    match err.get_ref().and_then(|e| e.downcast_ref::<AttemptErrors>()) {
+        Some(errors) => errors.0.last().map_or(false, |(_, e)| is_fd_exhausted(e)),
+        None => false,
+    }
+}
+
+#[cfg(unix)]
+fn is_fd_exhausted_code(code: i32) -> bool {
+    code == ::libc::EMFILE || code == ::libc::ENFILE
+}
+
+#[cfg(windows)]
+fn is_fd_exhausted_code(code: i32) -> bool {
+    // WSAEMFILE
+    code == 10024
+}
+
+#[cfg(not(any(unix, windows)))]
+fn is_fd_exhausted_code(_: i32) -> bool {
+    false
+}
@@ -0,0 +1,207 @@
+//! Connection racing as described in RFC 8305.
+use socket2::Socket;
+use std::cmp;
+use std::io;
+use std::mem;
+use std::net::SocketAddr;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use {ConnectInfo, Connected, HttpTimeoutConnector, connect_socket, error, remaining};
+
+/// The default delay between the start of consecutive connection attempts.
+pub const CONNECTION_ATTEMPT_DELAY_MS: u64 = 250;
+
+pub fn connect(connector: &HttpTimeoutConnector,
+               addrs: Vec<SocketAddr>,
+               timeout: Option<Duration>,
+               deadline: Option<Instant>)
+               -> io::Result<Connected> {
+    race(connector,
+         interleave(addrs),
+         connector.happy_eyeballs_delay,
+         connector.max_parallel_attempts,
+         timeout,
+         deadline)
+}
+
+/// The index and address of a finished attempt, along with its result and
+/// how long it took.
+type Attempt = (usize, SocketAddr, io::Result<Socket>, Duration);
+
+/// Connects to the first address to accept a connection, starting attempts
+/// in order `delay` apart with at most `max_pending` in flight at once, each
+/// bounded by the connection timeout `timeout`.
+///
+/// Attempts which lose the race are not cancelled. Their threads keep
+/// running until the attempt completes or times out, and a connection made
+/// after the race is over is closed.
+pub fn race(connector: &HttpTimeoutConnector,
+            addrs: Vec<SocketAddr>,
+            delay: Duration,
+            max_pending: usize,
+            timeout: Option<Duration>,
+            deadline: Option<Instant>)
+            -> io::Result<Connected> {
+    let max_pending = cmp::max(max_pending, 1);
+    let (tx, rx) = mpsc::channel::<Attempt>();
+
+    let addresses = addrs.len();
+    let mut addrs = addrs.into_iter().enumerate().peekable();
+    let mut pending = 0;
+    let mut errors = vec![];
+    let mut attempts = vec![];
+
+    loop {
+        if pending < max_pending {
+            if let Some((index, addr)) = addrs.next() {
+                let timeout = match remaining(timeout, deadline) {
+                    Ok(timeout) => timeout,
+                    Err(e) => {
+                        errors.push((addr, e));
+                        break;
+                    }
+                };
+
+                match connector.socket(&addr) {
+                    Ok(socket) => {
+                        let tx = tx.clone();
+                        let strategy = connector.timeout_strategy;
+                        thread::spawn(move || {
+                            let start = Instant::now();
+                            let r = connect_socket(&socket, addr, timeout, strategy)
+                                .map(|()| socket);
+                            // the receiver is gone if another attempt already won,
+                            // in which case the socket is dropped and so closed
+                            let _ = tx.send((index, addr, r, start.elapsed()));
+                        });
+                        pending += 1;
+                    }
+                    Err(e) => {
+                        connector.attempt_failed(addr, &e, Duration::from_secs(0));
+                        attempts.push((addr, Duration::from_secs(0)));
+                        let exhausted = error::is_fd_exhausted(&e);
+                        errors.push((addr, e));
+                        if exhausted {
+                            break;
+                        }
+                        continue;
+                    }
+                }
+            }
+        }
+
+        if pending == 0 {
+            if addrs.peek().is_none() {
+                break;
+            }
+            continue;
+        }
+
+        // wait for an attempt to finish, moving on to the next address if
+        // the attempt delay elapses first and another attempt may be started
+        let r = if addrs.peek().is_some() && pending < max_pending {
+            match rx.recv_timeout(delay) {
+                Ok(r) => r,
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        } else {
+            match rx.recv() {
+                Ok(r) => r,
+                Err(_) => break,
+            }
+        };
+
+        pending -= 1;
+        if let Some(mut connected) = finished(connector, r, &mut attempts, &mut errors) {
+            connected.info.addresses = addresses;
+            return Ok(connected);
+        }
+    }
+
+    // attempts still running when the deadline passed are bounded by it too
+    while pending > 0 {
+        let r = match rx.recv() {
+            Ok(r) => r,
+            Err(_) => break,
+        };
+        pending -= 1;
+        if let Some(mut connected) = finished(connector, r, &mut attempts, &mut errors) {
+            connected.info.addresses = addresses;
+            return Ok(connected);
+        }
+    }
+
+    Err(error::attempts_failed(errors))
+}
+
+/// Records the outcome of an attempt, returning the connection if it
+/// succeeded.
+///
+/// An attempt which connects but can't then be set up is treated like any
+/// other failed attempt, so that the race continues with the others.
+fn finished(connector: &HttpTimeoutConnector,
+            (index, addr, r, elapsed): Attempt,
+            attempts: &mut Vec<(SocketAddr, Duration)>,
+            errors: &mut Vec<(SocketAddr, io::Error)>)
+            -> Option<Connected> {
+    attempts.push((addr, elapsed));
+    match r.and_then(|socket| connector.finish(socket)) {
+        Ok(stream) => {
+            connector.attempt_succeeded(addr, elapsed);
+            let mut info = ConnectInfo::new(addr, elapsed);
+            info.index = index;
+            info.failed = errors.len();
+            info.timing.attempts = mem::take(attempts);
+            Some(Connected { stream, info })
+        }
+        Err(e) => {
+            connector.attempt_failed(addr, &e, elapsed);
+            errors.push((addr, e));
+            None
+        }
+    }
+}
+
+/// Reorders addresses so that the address families alternate, starting with
+/// the family of the first address.
+pub fn interleave(addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let first_v6 = addrs.first().map_or(false, |a| a.is_ipv6());
+    let (preferred, other): (Vec<_>, Vec<_>) =
+        addrs.into_iter().partition(|a| a.is_ipv6() == first_v6);
+
+    let mut out = Vec::with_capacity(preferred.len() + other.len());
+    let mut preferred = preferred.into_iter();
+    let mut other = other.into_iter();
+    loop {
+        match (preferred.next(), other.next()) {
+            (None, None) => break,
+            (a, b) => {
+                out.extend(a);
+                out.extend(b);
+            }
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn interleave_families() {
+        let addrs = vec!["[::1]:80".parse().unwrap(),
+                         "[::2]:80".parse().unwrap(),
+                         "[::3]:80".parse().unwrap(),
+                         "127.0.0.1:80".parse().unwrap()];
+        let expected: Vec<SocketAddr> = vec!["[::1]:80".parse().unwrap(),
+                                             "127.0.0.1:80".parse().unwrap(),
+                                             "[::2]:80".parse().unwrap(),
+                                             "[::3]:80".parse().unwrap()];
+        assert_eq!(interleave(addrs), expected);
+    }
+}
@@ -11,6 +11,73 @@
 //! The timeout is applied separately to each of the IP addresses associated
 //! with the host.
 //!
+//! Hostname resolution is not covered by the connection timeout. A separate
+//! resolution timeout can be configured, in which case the total time taken by
+//! a connect call is bounded by the resolution timeout plus the connection
+//! timeout multiplied by the number of addresses associated with the host.
+//!
+//! A total timeout may also be configured which bounds the entire connect
+//! call, including resolution, regardless of the number of addresses tried.
+//! Each individual attempt is then limited by whichever of the connection
+//! timeout and the time remaining before the total timeout is shorter.
+//!
+//! By default, addresses are tried one after another in the order they were
+//! resolved. The connector can instead be configured to race connection
+//! attempts to IPv4 and IPv6 addresses following the "Happy Eyeballs"
+//! algorithm described in [RFC 8305](https://tools.ietf.org/html/rfc8305).
+//!
+//! # IPv6 scope IDs
+//!
+//! Scope IDs (zone indices) of IPv6 addresses are preserved when connecting,
+//! so link-local addresses can be used. A host such as `fe80::1%eth0` is
+//! passed to the system resolver, which on most platforms resolves it to an
+//! address with the scope ID of the named interface. Custom resolvers can
+//! return `SocketAddrV6` values with a scope ID directly. When binding to a
+//! link-local local address, the scope ID of the remote address is used.
+//!
+//! # Proxies
+//!
+//! Connections can also be tunneled through an HTTP proxy with the `CONNECT`
+//! method or through a SOCKS5 proxy, in which case the connector's
+//! configuration applies to the connection to the proxy. The connection and
+//! total timeouts bound the establishment of the tunnel as well as the
+//! connection to the proxy.
+//!
+//! # Pinning hosts to addresses
+//!
+//! `HttpTimeoutConnector::add_host_override` pins a host to specific
+//! addresses, so that connections to it go to a chosen backend, such as one
+//! member of a pool behind a virtual IP, without resolving it. This is only
+//! the connector's half of pinning: Hyper still derives the `Host` header
+//! from the URL, and an `HttpsConnector` still uses the host in the URL for
+//! TLS server name indication and certificate validation, so the request is
+//! handled exactly as one to the logical host except for where the TCP
+//! connection goes.
+//!
+//! ```no_run
+//! extern crate hyper;
+//! extern crate hyper_timeout_connector;
+//!
+//! use hyper::Client;
+//! use hyper_timeout_connector::HttpTimeoutConnector;
+//!
+//! fn main() {
+//!     let mut connector = HttpTimeoutConnector::new();
+//!     connector.add_host_override("service.internal".to_owned(),
+//!                                 vec!["10.0.0.12".parse().unwrap()]);
+//!     let client = Client::with_connector(connector);
+//!
+//!     // sent to 10.0.0.12 with a `Host: service.internal` header
+//!     let response = client.get("http://service.internal").send().unwrap();
+//! }
+//! ```
+//!
+//! # Logging
+//!
+//! If the `logging` Cargo feature is enabled, each connection attempt is
+//! logged at the debug level via the `log` crate, and connect calls which fail
+//! entirely are logged at the warn level.
+//!
 //! # Examples
 //!
 //! Connecting to HTTP sites:
@@ -53,28 +120,481 @@
 //!
 //!     let response = client.get("https://google.com").send().unwrap();
 //! }
+//! ```
+//!
+//! The connection timeout does not cover the TLS handshake performed by an
+//! `HttpsConnector`. A handshake timeout can be set to bound it, which
+//! `hyper::Client` replaces with its own read and write timeouts once the
+//! connection is established:
+//!
+//! ```ignore
+//! let mut connector = HttpTimeoutConnector::new();
+//! connector.set_connect_timeout(Some(Duration::from_secs(30)));
+//! connector.set_handshake_timeout(Some(Duration::from_secs(10)));
+//!
+//! let connector = HttpsConnector::with_connector(make_ssl_client(), connector);
+//! let client = Client::with_connector(connector);
+//! ```
 #![doc(html_root_url="https://docs.rs/hyper-timeout-connector/0.1.0")]
 #![warn(missing_docs)]
 extern crate hyper;
 extern crate socket2;
+#[cfg(unix)]
+extern crate libc;
+#[cfg(feature = "logging")]
+extern crate log;
 
 use hyper::net::{NetworkConnector, HttpStream};
-use std::time::Duration;
-use std::net::{TcpStream, SocketAddr, ToSocketAddrs};
+use std::time::{Duration, Instant};
+use std::net::{Shutdown, TcpStream, SocketAddr, SocketAddrV6, ToSocketAddrs, IpAddr, Ipv4Addr,
+               Ipv6Addr};
+use std::env;
+use std::ffi::OsString;
+use std::cmp::{self, Ordering};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering as AtomicOrdering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread::{self, JoinHandle};
 use socket2::{SockAddr, Socket, Domain, Type};
-use std::io;
+use std::io::{self, Write};
+
+#[cfg(feature = "logging")]
+macro_rules! debug {
+    ($($t:tt)*) => (::log::debug!($($t)*))
+}
+
+#[cfg(not(feature = "logging"))]
+macro_rules! debug {
+    ($($t:tt)*) => (if false { let _ = format_args!($($t)*); })
+}
+
+#[cfg(feature = "logging")]
+macro_rules! warn {
+    ($($t:tt)*) => (::log::warn!($($t)*))
+}
+
+#[cfg(not(feature = "logging"))]
+macro_rules! warn {
+    ($($t:tt)*) => (if false { let _ = format_args!($($t)*); })
+}
+
+pub use error::{AttemptErrors, ConnectError};
+pub use plan::{ConnectionPlan, PlannedAddress};
+pub use proxy::ProxyConfig;
+pub use proxy_protocol::ProxyProtocolVersion;
+pub use stats::{ConnectorStats, LatencyStats};
+pub use wrapper::WrappedConnector;
+#[cfg(unix)]
+pub use unix::{UnixStream, UnixTimeoutConnector};
+
+mod cache;
+mod error;
+mod happy_eyeballs;
+mod limit;
+mod plan;
+mod proxy;
+mod proxy_protocol;
+mod rng;
+mod stats;
+mod sys;
+#[cfg(unix)]
+mod unix;
+mod wrapper;
+
+/// Controls which address families are used to connect to a host.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AddressFamily {
+    /// Use all addresses in the order they were resolved.
+    Any,
+    /// Only use IPv4 addresses.
+    Ipv4Only,
+    /// Only use IPv6 addresses.
+    Ipv6Only,
+    /// Try IPv4 addresses before IPv6 addresses.
+    PreferIpv4,
+    /// Try IPv6 addresses before IPv4 addresses.
+    PreferIpv6,
+}
+
+/// Controls how connection timeouts are enforced.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TimeoutStrategy {
+    /// Connect in nonblocking mode and wait for the connection to be
+    /// established with `poll` or `select`.
+    Native,
+    /// Connect in blocking mode on the calling thread while a separate
+    /// watchdog thread shuts the socket down if the timeout elapses.
+    ///
+    /// This relies on the platform aborting a blocked connect when the socket
+    /// is shut down, and costs a thread per connection attempt.
+    WatchdogThread,
+}
+
+/// The interval at which cancellable connect calls check for cancellation.
+const CANCEL_POLL_INTERVAL_MS: u64 = 10;
+
+type Resolver = dyn Fn(&str, u16) -> io::Result<Vec<SocketAddr>> + Send + Sync;
+type AddressSort = dyn Fn(&SocketAddr, &SocketAddr) -> Ordering + Send + Sync;
+type AddressFilter = dyn Fn(&SocketAddr) -> bool + Send + Sync;
+type SocketConfigurator = dyn Fn(&Socket) -> io::Result<()> + Send + Sync;
+type SuccessHook = dyn Fn(SocketAddr, Duration) + Send + Sync;
+type FailureHook = dyn Fn(SocketAddr, &io::Error) + Send + Sync;
+type ResolveHook = dyn Fn(&str, &[SocketAddr]) + Send + Sync;
+
+/// Information about how a connection was established.
+#[derive(Clone, Debug)]
+pub struct ConnectInfo {
+    addr: SocketAddr,
+    local_addr: Option<SocketAddr>,
+    index: usize,
+    addresses: usize,
+    failed: usize,
+    elapsed: Duration,
+    cached: bool,
+    keepalive: Option<Duration>,
+    user_timeout: Option<Duration>,
+    timing: ConnectTiming,
+}
+
+impl ConnectInfo {
+    fn new(addr: SocketAddr, elapsed: Duration) -> ConnectInfo {
+        ConnectInfo {
+            addr,
+            local_addr: None,
+            index: 0,
+            addresses: 1,
+            failed: 0,
+            elapsed,
+            cached: false,
+            keepalive: None,
+            user_timeout: None,
+            timing: ConnectTiming::default(),
+        }
+    }
+
+    /// Returns the address that was connected to.
+    ///
+    /// When connecting through a proxy, this is the address of the proxy.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Returns the local address of the connection.
+    ///
+    /// This is the address and port assigned by the operating system, which
+    /// can be used to correlate connections with firewall or NAT logs. It is
+    /// `None` if the local address could not be determined. The local
+    /// address of any stream is also available through the `TcpStream`
+    /// wrapped by `HttpStream`.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addr
+    }
+
+    /// Returns the position of the connected address among the addresses
+    /// which were tried, starting at 0.
+    ///
+    /// Addresses are counted in the order their attempts were started, which
+    /// differs from the resolved order when Happy Eyeballs is enabled.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the number of addresses which could have been tried.
+    pub fn addresses(&self) -> usize {
+        self.addresses
+    }
+
+    /// Returns the number of attempts to other addresses which failed before
+    /// the connection was established.
+    ///
+    /// Only the final round of attempts is counted if retries are enabled.
+    /// When Happy Eyeballs is enabled, attempts still in progress when the
+    /// connection was established are not counted.
+    pub fn failed_attempts(&self) -> usize {
+        self.failed
+    }
+
+    /// Returns the time taken by the successful connection attempt.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Returns whether the addresses of the host were taken from the DNS
+    /// cache rather than resolved.
+    ///
+    /// This is always `false` if the DNS cache is disabled, or if no
+    /// resolution was needed.
+    pub fn resolved_from_cache(&self) -> bool {
+        self.cached
+    }
+
+    /// Returns the TCP keepalive idle time set on the connection, or `None`
+    /// if keepalive is not enabled.
+    ///
+    /// Idle connections with keepalive enabled are closed promptly if the
+    /// peer goes away, so connection pools can safely keep them for longer.
+    pub fn keepalive(&self) -> Option<Duration> {
+        self.keepalive
+    }
+
+    /// Returns the TCP user timeout set on the connection, if any.
+    pub fn user_timeout(&self) -> Option<Duration> {
+        self.user_timeout
+    }
+}
+
+/// A breakdown of the time taken by a connect call, as returned by
+/// `HttpTimeoutConnector::connect_detailed`.
+#[derive(Clone, Debug, Default)]
+pub struct ConnectTiming {
+    resolve: Duration,
+    connect: Duration,
+    attempts: Vec<(SocketAddr, Duration)>,
+}
+
+impl ConnectTiming {
+    /// Returns the time taken to resolve the host.
+    ///
+    /// This includes time spent waiting for the resolver, and is close to
+    /// zero if the addresses were taken from the DNS cache. When connecting
+    /// through a proxy, it is the time taken to resolve the proxy's host.
+    pub fn resolve(&self) -> Duration {
+        self.resolve
+    }
+
+    /// Returns the time from the end of resolution until the connection was
+    /// established.
+    ///
+    /// This covers every connection attempt made, including retries and the
+    /// waits between them, and any proxy handshake.
+    pub fn connect(&self) -> Duration {
+        self.connect
+    }
+
+    /// Returns the addresses which were tried, in the order their attempts
+    /// finished, along with the time taken by each attempt.
+    ///
+    /// The last entry is the successful attempt. Only the final round of
+    /// attempts is included if retries are enabled, and attempts still in
+    /// progress when the connection was established are not included.
+    pub fn attempts(&self) -> &[(SocketAddr, Duration)] {
+        &self.attempts
+    }
+}
+
+/// A successfully established connection.
+#[derive(Debug)]
+struct Connected {
+    stream: TcpStream,
+    info: ConnectInfo,
+}
 
 /// A Hyper `NetworkConnector` which offers a connction timeout.
+#[derive(Clone)]
 pub struct HttpTimeoutConnector {
     connect_timeout: Option<Duration>,
+    resolve_timeout: Option<Duration>,
+    total_timeout: Option<Duration>,
+    happy_eyeballs: bool,
+    nodelay: bool,
+    keepalive: Option<Duration>,
+    local_address: Option<SocketAddr>,
+    bind_device: Option<String>,
+    reuse_address: bool,
+    reuse_port: bool,
+    ttl: Option<u32>,
+    address_family: AddressFamily,
+    resolver: Option<Arc<Resolver>>,
+    host_overrides: HashMap<String, Vec<IpAddr>>,
+    dns_cache_ttl: Option<Duration>,
+    dns_cache: Arc<cache::DnsCache>,
+    max_attempts: Option<usize>,
+    shuffle_addresses: bool,
+    read_timeout: Option<Duration>,
+    write_timeout: Option<Duration>,
+    linger: Option<Option<Duration>>,
+    retries: usize,
+    per_address_retries: usize,
+    retry_backoff: Duration,
+    retry_jitter: f64,
+    socket_configurator: Option<Arc<SocketConfigurator>>,
+    tos: Option<u32>,
+    connect_success_hook: Option<Arc<SuccessHook>>,
+    connect_failure_hook: Option<Arc<FailureHook>>,
+    resolve_hook: Option<Arc<ResolveHook>>,
+    proxy: Option<ProxyConfig>,
+    send_buffer_size: Option<usize>,
+    recv_buffer_size: Option<usize>,
+    allowed_schemes: Vec<String>,
+    strict_scheme: bool,
+    user_timeout: Option<Duration>,
+    v6_fallback_timeout: Option<Duration>,
+    address_sort: Option<Arc<AddressSort>>,
+    address_filter: Option<Arc<AddressFilter>>,
+    block_private_addresses: bool,
+    scheme_connect_timeouts: HashMap<String, Duration>,
+    max_parallel_attempts: usize,
+    parallel_attempts: usize,
+    local_port_range: Option<(u16, u16)>,
+    tcp_fastopen: bool,
+    fallback_host: Option<(String, u16)>,
+    disable_ipv6: bool,
+    stats: Arc<stats::Stats>,
+    timeout_strategy: TimeoutStrategy,
+    happy_eyeballs_delay: Duration,
+    proxy_protocol: Option<ProxyProtocolVersion>,
+    congestion_control: Option<String>,
+    ignore_option_errors: bool,
+    nonblocking_stream: bool,
+    hosts: Vec<(String, u16)>,
+    next_host: Arc<AtomicUsize>,
+    sticky_addresses: bool,
+    sticky_addrs: Arc<cache::StickyAddrs>,
+    freebind: bool,
+    transparent: bool,
+    address_cooldown: Option<Duration>,
+    failed_addrs: Arc<cache::FailedAddrs>,
+    handshake_timeout: Option<Duration>,
+    proxy_bypass: Vec<String>,
+    mss: Option<u32>,
+    priority: Option<u32>,
+    max_concurrent_connects: Option<usize>,
+    connect_queue_timeout: Option<Duration>,
+    in_flight: Arc<limit::InFlight>,
 }
 
 impl HttpTimeoutConnector {
     /// Creates a new `HttpTimeoutConnector`.
     ///
-    /// The connector initially has no connection timeout.
+    /// The connector initially has no timeouts.
     pub fn new() -> HttpTimeoutConnector {
-        HttpTimeoutConnector { connect_timeout: None }
+        HttpTimeoutConnector {
+            connect_timeout: None,
+            resolve_timeout: None,
+            total_timeout: None,
+            happy_eyeballs: false,
+            nodelay: false,
+            keepalive: None,
+            local_address: None,
+            bind_device: None,
+            reuse_address: false,
+            reuse_port: false,
+            ttl: None,
+            address_family: AddressFamily::Any,
+            resolver: None,
+            host_overrides: HashMap::new(),
+            dns_cache_ttl: None,
+            dns_cache: Arc::new(cache::DnsCache::new()),
+            max_attempts: None,
+            shuffle_addresses: false,
+            read_timeout: None,
+            write_timeout: None,
+            linger: None,
+            retries: 0,
+            per_address_retries: 0,
+            retry_backoff: Duration::from_millis(100),
+            retry_jitter: 0.0,
+            socket_configurator: None,
+            tos: None,
+            connect_success_hook: None,
+            connect_failure_hook: None,
+            resolve_hook: None,
+            proxy: None,
+            send_buffer_size: None,
+            recv_buffer_size: None,
+            allowed_schemes: vec!["http".to_owned()],
+            strict_scheme: true,
+            user_timeout: None,
+            v6_fallback_timeout: None,
+            address_sort: None,
+            address_filter: None,
+            block_private_addresses: false,
+            scheme_connect_timeouts: HashMap::new(),
+            max_parallel_attempts: 3,
+            parallel_attempts: 1,
+            local_port_range: None,
+            tcp_fastopen: false,
+            fallback_host: None,
+            disable_ipv6: false,
+            stats: Arc::new(stats::Stats::new()),
+            timeout_strategy: TimeoutStrategy::Native,
+            happy_eyeballs_delay:
+                Duration::from_millis(happy_eyeballs::CONNECTION_ATTEMPT_DELAY_MS),
+            proxy_protocol: None,
+            congestion_control: None,
+            ignore_option_errors: false,
+            nonblocking_stream: false,
+            hosts: vec![],
+            next_host: Arc::new(AtomicUsize::new(0)),
+            sticky_addresses: false,
+            sticky_addrs: Arc::new(cache::StickyAddrs::new()),
+            freebind: false,
+            transparent: false,
+            address_cooldown: None,
+            failed_addrs: Arc::new(cache::FailedAddrs::new()),
+            handshake_timeout: None,
+            proxy_bypass: vec![],
+            mss: None,
+            priority: None,
+            max_concurrent_connects: None,
+            connect_queue_timeout: None,
+            in_flight: Arc::new(limit::InFlight::new()),
+        }
+    }
+
+    /// Returns a builder for an `HttpTimeoutConnector`.
+    pub fn builder() -> HttpTimeoutConnectorBuilder {
+        HttpTimeoutConnectorBuilder(HttpTimeoutConnector::new())
+    }
+
+    /// Creates a new `HttpTimeoutConnector` with timeouts read from
+    /// environment variables.
+    ///
+    /// Each variable holds a timeout as a whole number of milliseconds:
+    ///
+    /// * `HTTP_CONNECT_TIMEOUT_MS` sets the connection timeout.
+    /// * `HTTP_RESOLVE_TIMEOUT_MS` sets the resolve timeout.
+    /// * `HTTP_TOTAL_TIMEOUT_MS` sets the total timeout.
+    /// * `HTTP_READ_TIMEOUT_MS` sets the read timeout.
+    /// * `HTTP_WRITE_TIMEOUT_MS` sets the write timeout.
+    ///
+    /// Timeouts whose variables are unset or empty keep their defaults. If a
+    /// variable is set to anything other than a number of milliseconds, an
+    /// `InvalidInput` error naming the variable is returned.
+    pub fn from_env() -> io::Result<HttpTimeoutConnector> {
+        HttpTimeoutConnector::from_vars(|name| env::var_os(name))
+    }
+
+    fn from_vars<F>(var: F) -> io::Result<HttpTimeoutConnector>
+        where F: Fn(&str) -> Option<OsString>
+    {
+        let timeout = |name: &str| -> io::Result<Option<Duration>> {
+            let value = match var(name) {
+                Some(ref value) if value.is_empty() => return Ok(None),
+                Some(value) => value,
+                None => return Ok(None),
+            };
+            match value.to_str().and_then(|v| v.trim().parse().ok()) {
+                Some(millis) => Ok(Some(Duration::from_millis(millis))),
+                None => {
+                    let msg = format!("invalid value {:?} for environment variable `{}`, expected \
+                                       a number of milliseconds",
+                                      value,
+                                      name);
+                    Err(io::Error::new(io::ErrorKind::InvalidInput, msg))
+                }
+            }
+        };
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_connect_timeout(timeout("HTTP_CONNECT_TIMEOUT_MS")?);
+        connector.set_resolve_timeout(timeout("HTTP_RESOLVE_TIMEOUT_MS")?);
+        connector.set_total_timeout(timeout("HTTP_TOTAL_TIMEOUT_MS")?);
+        connector.set_read_timeout(timeout("HTTP_READ_TIMEOUT_MS")?);
+        connector.set_write_timeout(timeout("HTTP_WRITE_TIMEOUT_MS")?);
+        Ok(connector)
     }
 
     /// Returns the connection timeout.
@@ -83,73 +603,4585 @@ impl HttpTimeoutConnector {
     }
 
     /// Sets the connection timeout.
+    ///
+    /// A timeout of zero does not disable the timeout. Instead, every
+    /// connection attempt fails immediately with a `TimedOut` error without
+    /// contacting the remote host. Use `None` to connect without a timeout.
     pub fn set_connect_timeout(&mut self, timeout: Option<Duration>) {
         self.connect_timeout = timeout;
     }
 
-    fn connect_once(&self, addr: SocketAddr) -> io::Result<TcpStream> {
-        let domain = match addr {
-            SocketAddr::V4(_) => Domain::ipv4(),
-            SocketAddr::V6(_) => Domain::ipv6(),
-        };
-        let socket = Socket::new(domain, Type::stream(), None)?;
-        let addr = SockAddr::from(addr);
-        match self.connect_timeout {
-            Some(timeout) => socket.connect_timeout(&addr, timeout)?,
-            None => socket.connect(&addr)?,
-        }
+    /// Returns whether any timeout is configured.
+    ///
+    /// This is true if a connection timeout (including one for a specific URL
+    /// scheme), resolution timeout, total timeout, read timeout, or write
+    /// timeout is set.
+    pub fn has_timeout(&self) -> bool {
+        self.connect_timeout.is_some() || !self.scheme_connect_timeouts.is_empty() ||
+        self.resolve_timeout.is_some() || self.total_timeout.is_some() ||
+        self.read_timeout.is_some() || self.write_timeout.is_some()
+    }
 
-        Ok(socket.into())
+    /// Returns how connection timeouts are enforced.
+    pub fn timeout_strategy(&self) -> TimeoutStrategy {
+        self.timeout_strategy
     }
-}
 
-impl NetworkConnector for HttpTimeoutConnector {
-    type Stream = HttpStream;
+    /// Sets how connection timeouts are enforced.
+    ///
+    /// The native strategy is reliable on all major platforms, but the
+    /// watchdog thread strategy can be used on platforms where nonblocking
+    /// connects misbehave. Defaults to `TimeoutStrategy::Native`.
+    pub fn set_timeout_strategy(&mut self, strategy: TimeoutStrategy) {
+        self.timeout_strategy = strategy;
+    }
 
-    fn connect(&self, host: &str, port: u16, scheme: &str) -> hyper::Result<HttpStream> {
-        if scheme != "http" {
-            return Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid scheme for http")
-                           .into());
+    /// Returns the connection timeout used for connect calls with the given
+    /// URL scheme.
+    ///
+    /// This is the timeout set for the scheme, if any, and the connection
+    /// timeout otherwise.
+    pub fn connect_timeout_for_scheme(&self, scheme: &str) -> Option<Duration> {
+        match self.scheme_connect_timeouts.get(scheme) {
+            Some(&timeout) => Some(timeout),
+            None => self.connect_timeout,
         }
+    }
 
-        let mut last_err = None;
-        for addr in (host, port).to_socket_addrs()? {
-            match self.connect_once(addr) {
-                Ok(l) => return Ok(HttpStream(l)),
-                Err(e) => last_err = Some(e),
+    /// Sets the connection timeout used for connect calls with the given URL
+    /// scheme.
+    ///
+    /// This overrides the connection timeout for the scheme, which allows a
+    /// single connector to use different timeouts for the connections made
+    /// by an `HttpsConnector` and plain HTTP connections, for example. The
+    /// scheme must still be accepted by the connector. Setting `None` removes
+    /// the override, so the connection timeout is used again.
+    pub fn set_connect_timeout_for_scheme(&mut self, scheme: &str, timeout: Option<Duration>) {
+        match timeout {
+            Some(timeout) => {
+                self.scheme_connect_timeouts.insert(scheme.to_owned(), timeout);
+            }
+            None => {
+                self.scheme_connect_timeouts.remove(scheme);
             }
         }
+    }
 
-        Err(last_err
-                .unwrap_or_else(|| {
-                                    io::Error::new(io::ErrorKind::InvalidInput,
-                                                   "could not resolve to any addresses")
-                                })
-                .into())
+    /// Returns the read timeout set on new connections.
+    pub fn read_timeout(&self) -> Option<Duration> {
+        self.read_timeout
     }
-}
 
-#[cfg(test)]
-mod test {
-    use hyper::{self, Client};
+    /// Sets the read timeout set on new connections.
+    ///
+    /// Unlike the connection timeout, this applies to reads made once the
+    /// connection has been established.
+    ///
+    /// Note that `hyper::Client` overrides this with its own read timeout
+    /// (see `Client::set_read_timeout`) before sending each request.
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) {
+        self.read_timeout = timeout;
+    }
 
-    use super::*;
+    /// Returns the write timeout set on new connections.
+    pub fn write_timeout(&self) -> Option<Duration> {
+        self.write_timeout
+    }
 
-    #[test]
-    fn timeout() {
-        let mut connector = HttpTimeoutConnector::new();
-        connector.set_connect_timeout(Some(Duration::from_millis(250)));
+    /// Sets the write timeout set on new connections.
+    ///
+    /// Unlike the connection timeout, this applies to writes made once the
+    /// connection has been established.
+    ///
+    /// Note that `hyper::Client` overrides this with its own write timeout
+    /// (see `Client::set_write_timeout`) before sending each request.
+    pub fn set_write_timeout(&mut self, timeout: Option<Duration>) {
+        self.write_timeout = timeout;
+    }
 
-        let client = Client::with_connector(connector);
+    /// Returns the timeout set on new connections for the handshake of a
+    /// wrapping protocol such as TLS.
+    pub fn handshake_timeout(&self) -> Option<Duration> {
+        self.handshake_timeout
+    }
 
-        // this is an unroutable IP so connection should always time out
-        match client.get("http://10.255.255.1:80").send() {
-            Ok(_) => panic!("unexpected success"),
-            Err(hyper::Error::Io(ref e)) if e.kind() == io::ErrorKind::TimedOut => {}
-            Err(e) => panic!("unexpected error {:?}", e),
+    /// Sets the timeout set on new connections for the handshake of a
+    /// wrapping protocol such as TLS.
+    ///
+    /// If set, this is used as both the read and write timeout of new
+    /// connections in place of the read and write timeouts, so that the
+    /// reads and writes made by a TLS handshake in an `HttpsConnector` cannot
+    /// block indefinitely. Once the handshake is complete, the timeouts
+    /// should be reset with `clear_handshake_timeout`. `hyper::Client` does
+    /// this itself, as it sets its own read and write timeouts before sending
+    /// each request. Defaults to `None`.
+    pub fn set_handshake_timeout(&mut self, timeout: Option<Duration>) {
+        self.handshake_timeout = timeout;
+    }
+
+    /// Replaces the handshake timeout set on a connection with the
+    /// connector's read and write timeouts.
+    pub fn clear_handshake_timeout(&self, stream: &TcpStream) -> io::Result<()> {
+        stream.set_read_timeout(self.read_timeout)?;
+        stream.set_write_timeout(self.write_timeout)
+    }
+
+    /// Sets the read and write timeouts of a newly established connection.
+    fn set_stream_timeouts(&self, stream: &TcpStream) -> io::Result<()> {
+        match self.handshake_timeout {
+            Some(timeout) => {
+                stream.set_read_timeout(Some(timeout))?;
+                stream.set_write_timeout(Some(timeout))
+            }
+            None => self.clear_handshake_timeout(stream),
         }
     }
 
+    /// Returns whether new connections are returned in nonblocking mode.
+    pub fn nonblocking_stream(&self) -> bool {
+        self.nonblocking_stream
+    }
+
+    /// Sets whether new connections are returned in nonblocking mode.
+    ///
+    /// This only affects the mode of the stream once the connection has been
+    /// established, and any proxy handshake completed. Hyper expects streams
+    /// to block, so this should only be enabled when using the connector
+    /// directly. Defaults to `false`.
+    pub fn set_nonblocking_stream(&mut self, nonblocking: bool) {
+        self.nonblocking_stream = nonblocking;
+    }
+
+    /// Returns the hostname resolution timeout.
+    pub fn resolve_timeout(&self) -> Option<Duration> {
+        self.resolve_timeout
+    }
+
+    /// Sets the hostname resolution timeout.
+    ///
+    /// If set, resolution is performed on a separate thread, and the connect
+    /// call fails with a `TimedOut` error if it does not complete in time.
+    pub fn set_resolve_timeout(&mut self, timeout: Option<Duration>) {
+        self.resolve_timeout = timeout;
+    }
+
+    /// Returns the total timeout.
+    pub fn total_timeout(&self) -> Option<Duration> {
+        self.total_timeout
+    }
+
+    /// Sets the total timeout.
+    ///
+    /// This bounds the entire connect call, including hostname resolution and
+    /// the connection attempts to every address associated with the host.
+    pub fn set_total_timeout(&mut self, timeout: Option<Duration>) {
+        self.total_timeout = timeout;
+    }
+
+    /// Returns whether Happy Eyeballs connection racing is enabled.
+    pub fn happy_eyeballs(&self) -> bool {
+        self.happy_eyeballs
+    }
+
+    /// Enables or disables Happy Eyeballs connection racing.
+    ///
+    /// When enabled, the resolved addresses are interleaved by address family
+    /// and connection attempts are started a short delay apart without
+    /// waiting for earlier attempts to complete, up to the maximum number of
+    /// parallel attempts. The first connection to be established is used and
+    /// the others are abandoned.
+    ///
+    /// Each attempt runs on its own thread. Abandoned attempts are not
+    /// cancelled, so their threads keep running until the attempt completes
+    /// or hits the connection timeout, and any connection they make is then
+    /// closed. Defaults to `false`.
+    pub fn set_happy_eyeballs(&mut self, happy_eyeballs: bool) {
+        self.happy_eyeballs = happy_eyeballs;
+    }
+
+    /// Returns the delay between the start of consecutive Happy Eyeballs
+    /// connection attempts.
+    pub fn happy_eyeballs_delay(&self) -> Duration {
+        self.happy_eyeballs_delay
+    }
+
+    /// Sets the delay between the start of consecutive Happy Eyeballs
+    /// connection attempts.
+    ///
+    /// Defaults to 250 milliseconds, as recommended by RFC 8305. A shorter
+    /// delay suits low latency networks such as those within a datacenter,
+    /// but makes it more likely that several attempts run in parallel, using
+    /// more threads and sockets and putting more load on the remote host.
+    pub fn set_happy_eyeballs_delay(&mut self, delay: Duration) {
+        self.happy_eyeballs_delay = delay;
+    }
+
+    /// Returns the maximum number of connection attempts Happy Eyeballs keeps
+    /// in flight at once.
+    pub fn max_parallel_attempts(&self) -> usize {
+        self.max_parallel_attempts
+    }
+
+    /// Sets the maximum number of connection attempts Happy Eyeballs keeps in
+    /// flight at once.
+    ///
+    /// Once the limit is reached, the next attempt is only started when an
+    /// earlier one fails, which bounds the number of threads used to connect
+    /// to hosts with many addresses. A value of 0 is treated as 1. Defaults to
+    /// 3.
+    pub fn set_max_parallel_attempts(&mut self, max_parallel_attempts: usize) {
+        self.max_parallel_attempts = max_parallel_attempts;
+    }
+
+    /// Returns the number of connection attempts started at once when Happy
+    /// Eyeballs is disabled.
+    pub fn parallel_attempts(&self) -> usize {
+        self.parallel_attempts
+    }
+
+    /// Sets the number of connection attempts started at once when Happy
+    /// Eyeballs is disabled.
+    ///
+    /// Attempts to the first addresses are started together, without the
+    /// staggering or address family interleaving of Happy Eyeballs. Whenever
+    /// an attempt fails the next address is tried, so that up to this many
+    /// attempts are in flight until one succeeds. The first connection to be
+    /// established is used and the others are abandoned, as with Happy
+    /// Eyeballs. Each attempt runs on its own thread, and the IPv6 fallback
+    /// timeout is not applied.
+    ///
+    /// This has no effect if Happy Eyeballs is enabled. A value of 0 is
+    /// treated as 1. Defaults to 1, which tries addresses one at a time.
+    pub fn set_parallel_attempts(&mut self, parallel_attempts: usize) {
+        self.parallel_attempts = parallel_attempts;
+    }
+
+    /// Returns the timeout of the first IPv6 connection attempt to hosts with
+    /// both IPv4 and IPv6 addresses.
+    pub fn v6_fallback_timeout(&self) -> Option<Duration> {
+        self.v6_fallback_timeout
+    }
+
+    /// Sets the timeout of the first IPv6 connection attempt to hosts with
+    /// both IPv4 and IPv6 addresses.
+    ///
+    /// This is a simpler and deterministic alternative to Happy Eyeballs. If
+    /// a host resolves to addresses of both families, the first attempt to an
+    /// IPv6 address is limited to this timeout, so that a broken IPv6 path
+    /// quickly falls back to IPv4. All other attempts use the connection
+    /// timeout. The fallback timeout applies after addresses have been
+    /// filtered and ordered by the address family configuration, so it has no
+    /// effect with `AddressFamily::Ipv4Only` or `AddressFamily::Ipv6Only`. It
+    /// is ignored if Happy Eyeballs is enabled. Defaults to `None`.
+    pub fn set_v6_fallback_timeout(&mut self, timeout: Option<Duration>) {
+        self.v6_fallback_timeout = timeout;
+    }
+
+    /// Returns whether `TCP_NODELAY` is set on new connections.
+    pub fn nodelay(&self) -> bool {
+        self.nodelay
+    }
+
+    /// Sets whether `TCP_NODELAY` is set on new connections, disabling Nagle's
+    /// algorithm.
+    ///
+    /// Defaults to `false`, which leaves the operating system's default in
+    /// place.
+    pub fn set_nodelay(&mut self, nodelay: bool) {
+        self.nodelay = nodelay;
+    }
+
+    /// Returns the TCP keepalive idle time set on new connections.
+    pub fn keepalive(&self) -> Option<Duration> {
+        self.keepalive
+    }
+
+    /// Sets the TCP keepalive idle time for new connections.
+    ///
+    /// If set, `SO_KEEPALIVE` is enabled and keepalive probes are sent after
+    /// the connection has been idle for the specified duration, rounded down
+    /// to whole seconds on Unix. Defaults to `None`, which leaves the
+    /// operating system's default in place.
+    pub fn set_keepalive(&mut self, keepalive: Option<Duration>) {
+        self.keepalive = keepalive;
+    }
+
+    /// Returns the local address new connections are bound to.
+    pub fn local_address(&self) -> Option<SocketAddr> {
+        self.local_address
+    }
+
+    /// Sets the local address new connections are bound to.
+    ///
+    /// The socket is bound to the address before connecting. If the port is
+    /// nonzero the socket is bound to exactly that port, taking precedence
+    /// over the local port range. Otherwise the port is picked from the local
+    /// port range if one is set, and assigned by the OS if not. Attempts to
+    /// connect to addresses of a different family than the local address fail
+    /// with an `InvalidInput` error, so those addresses are effectively
+    /// skipped. Defaults to `None`.
+    ///
+    /// A fixed port can only be used by one connection at a time, so
+    /// concurrent connects fail with an `AddrInUse` error unless
+    /// `SO_REUSEADDR` or `SO_REUSEPORT` allow the port to be shared. Even
+    /// then, only one connection to a given remote address can use the port.
+    pub fn set_local_address(&mut self, addr: Option<SocketAddr>) {
+        self.local_address = addr;
+    }
+
+    /// Returns the range of local ports new connections are bound to.
+    pub fn local_port_range(&self) -> Option<(u16, u16)> {
+        self.local_port_range
+    }
+
+    /// Sets the inclusive range of local ports new connections are bound to.
+    ///
+    /// Before connecting, the socket is bound to a port in the range, starting
+    /// from a random port and moving on to the next one if a port is already
+    /// in use. The local address is used if one is configured, and the
+    /// unspecified address otherwise. If every port in the range is in use,
+    /// the attempt fails with an `AddrInUse` error. Defaults to `None`, which
+    /// uses an OS-assigned port.
+    pub fn set_local_port_range(&mut self, range: Option<(u16, u16)>) {
+        self.local_port_range = range;
+    }
+
+    /// Returns the network interface new connections are bound to.
+    pub fn bind_device(&self) -> Option<&str> {
+        self.bind_device.as_deref()
+    }
+
+    /// Sets the network interface new connections are bound to, such as
+    /// `eth1`.
+    ///
+    /// This sets `SO_BINDTODEVICE`, which is only supported on Linux and
+    /// typically requires elevated privileges. On other platforms, connect
+    /// calls fail with an `Unsupported` error if a device is configured.
+    /// Defaults to `None`.
+    pub fn set_bind_device(&mut self, device: Option<String>) {
+        self.bind_device = device;
+    }
+
+    /// Returns whether `SO_REUSEADDR` is set on new connections.
+    pub fn reuse_address(&self) -> bool {
+        self.reuse_address
+    }
+
+    /// Sets whether `SO_REUSEADDR` is set on new connections.
+    ///
+    /// This is most useful in combination with a local address, allowing
+    /// local ports in the `TIME_WAIT` state to be reused. Defaults to `false`.
+    pub fn set_reuse_address(&mut self, reuse_address: bool) {
+        self.reuse_address = reuse_address;
+    }
+
+    /// Returns whether `SO_REUSEPORT` is set on new connections.
+    pub fn reuse_port(&self) -> bool {
+        self.reuse_port
+    }
+
+    /// Sets whether `SO_REUSEPORT` is set on new connections.
+    ///
+    /// Unlike `SO_REUSEADDR`, this allows multiple sockets which all set the
+    /// option to be bound to the same local address and port at once, as long
+    /// as they connect to different remote addresses. On Linux, sockets can
+    /// only share a port with sockets created by the same user.
+    ///
+    /// It is supported on Linux, Android, macOS, iOS, FreeBSD, DragonFly BSD,
+    /// NetBSD, and OpenBSD. On other platforms, including Windows, connect
+    /// calls fail with an `Unsupported` error if it is enabled. Defaults to
+    /// `false`.
+    pub fn set_reuse_port(&mut self, reuse_port: bool) {
+        self.reuse_port = reuse_port;
+    }
+
+    /// Returns whether new connections may be bound to non-local addresses.
+    pub fn freebind(&self) -> bool {
+        self.freebind
+    }
+
+    /// Sets whether new connections may be bound to non-local addresses.
+    ///
+    /// This sets `IP_FREEBIND`, which allows the local address to be one which
+    /// is not (yet) assigned to any interface. It is only supported on Linux,
+    /// and connect calls fail with an `Unsupported` error on other platforms
+    /// if it is enabled. Defaults to `false`.
+    pub fn set_freebind(&mut self, freebind: bool) {
+        self.freebind = freebind;
+    }
+
+    /// Returns whether new connections are made in transparent proxy mode.
+    pub fn transparent(&self) -> bool {
+        self.transparent
+    }
+
+    /// Sets whether new connections are made in transparent proxy mode.
+    ///
+    /// This sets `IP_TRANSPARENT` for IPv4 connections and `IPV6_TRANSPARENT`
+    /// for IPv6 connections, which allows the local address to be any
+    /// address, such as that of the client whose connection is being
+    /// forwarded. It requires the `CAP_NET_ADMIN` capability, and connect
+    /// calls fail with a `PermissionDenied` error without it. It is only
+    /// supported on Linux, and connect calls fail with an `Unsupported` error
+    /// on other platforms if it is enabled. Defaults to `false`.
+    pub fn set_transparent(&mut self, transparent: bool) {
+        self.transparent = transparent;
+    }
+
+    /// Returns the IP time-to-live set on new connections.
+    pub fn ttl(&self) -> Option<u32> {
+        self.ttl
+    }
+
+    /// Sets the IP time-to-live of packets sent over new connections.
+    ///
+    /// This sets `IP_TTL` for IPv4 connections and `IPV6_UNICAST_HOPS` for
+    /// IPv6 connections. Defaults to `None`, which leaves the operating
+    /// system's default in place.
+    pub fn set_ttl(&mut self, ttl: Option<u32>) {
+        self.ttl = ttl;
+    }
+
+    /// Returns the address family configuration.
+    pub fn address_family(&self) -> AddressFamily {
+        self.address_family
+    }
+
+    /// Sets which address families are used to connect to a host.
+    ///
+    /// Resolved addresses are filtered or reordered accordingly before any
+    /// connection attempts are made. Defaults to `AddressFamily::Any`.
+    pub fn set_address_family(&mut self, family: AddressFamily) {
+        self.address_family = family;
+    }
+
+    /// Returns whether IPv6 addresses are discarded after resolution.
+    pub fn disable_ipv6(&self) -> bool {
+        self.disable_ipv6
+    }
+
+    /// Sets whether IPv6 addresses are discarded after resolution.
+    ///
+    /// If enabled, IPv6 addresses are removed from the resolved addresses
+    /// before any other processing, regardless of the address family
+    /// configuration, so no connection attempts are ever made over IPv6. This
+    /// is intended for environments where IPv6 is advertised but broken. If a
+    /// host only has IPv6 addresses, connect calls fail with a
+    /// `ConnectError::NoAddresses` error. Defaults to `false`.
+    pub fn set_disable_ipv6(&mut self, disable_ipv6: bool) {
+        self.disable_ipv6 = disable_ipv6;
+    }
+
+    /// Returns the custom hostname resolver, if one is set.
+    pub fn resolver(&self) -> Option<&Resolver> {
+        self.resolver.as_deref()
+    }
+
+    /// Sets a custom hostname resolver.
+    ///
+    /// The resolver is called with the host and port being connected to and
+    /// returns the addresses to try. If no resolver is set, the system
+    /// resolver is used via `ToSocketAddrs`.
+    pub fn set_resolver(&mut self, resolver: Option<Box<Resolver>>) {
+        self.resolver = resolver.map(Arc::from);
+    }
+
+    /// Returns the addresses a host is overridden to resolve to, if any.
+    pub fn host_override(&self, host: &str) -> Option<&[IpAddr]> {
+        self.host_overrides.get(&host.to_ascii_lowercase()).map(|a| &**a)
+    }
+
+    /// Overrides the addresses a host resolves to, like an entry in
+    /// `/etc/hosts`.
+    ///
+    /// Connect calls to the host use the addresses, each combined with the
+    /// port being connected to, instead of resolving it. Overrides take
+    /// precedence over the DNS cache and custom resolver, but the addresses
+    /// are filtered and ordered like resolved addresses. Hosts are matched
+    /// case-insensitively, and a later override of the same host replaces an
+    /// earlier one.
+    ///
+    /// Only the connection is affected, so the `Host` header and TLS server
+    /// name used by Hyper are still those of the host. When connecting
+    /// through a proxy, the target host is resolved by the proxy, so only an
+    /// override of the proxy's host has any effect.
+    pub fn add_host_override(&mut self, host: String, addrs: Vec<IpAddr>) {
+        self.host_overrides.insert(host.to_ascii_lowercase(), addrs);
+    }
+
+    /// Removes the override of the addresses a host resolves to.
+    pub fn remove_host_override(&mut self, host: &str) {
+        self.host_overrides.remove(&host.to_ascii_lowercase());
+    }
+
+    /// Returns the comparator used to order resolved addresses, if one is set.
+    pub fn address_sort(&self) -> Option<&AddressSort> {
+        self.address_sort.as_deref()
+    }
+
+    /// Sets a comparator used to order resolved addresses.
+    ///
+    /// The addresses are sorted with the comparator after the address family
+    /// configuration has been applied, so it can be used to implement
+    /// arbitrary preferences such as favoring addresses on the local subnet.
+    /// The sort is stable, so addresses the comparator considers equal keep
+    /// their previous order. Defaults to `None`, which leaves the order
+    /// unchanged.
+    pub fn set_address_sort(&mut self, sort: Option<Box<AddressSort>>) {
+        self.address_sort = sort.map(Arc::from);
+    }
+
+    /// Returns the callback used to reject resolved addresses, if one is set.
+    pub fn address_filter(&self) -> Option<&AddressFilter> {
+        self.address_filter.as_deref()
+    }
+
+    /// Sets a callback used to reject resolved addresses.
+    ///
+    /// The callback is invoked with each address a host resolves to, and
+    /// addresses for which it returns `false` are never connected to. This
+    /// can be used to enforce policies such as refusing to connect to a
+    /// blocklist of addresses, which as the check happens after resolution
+    /// also applies to hostnames which resolve to those addresses. It applies
+    /// to the addresses of proxies and overridden hosts, but not to addresses
+    /// passed directly to `connect_addr` or `connect_addrs`.
+    ///
+    /// If every address of a host is rejected, connect calls fail with a
+    /// `ConnectError::NoAddresses` error of kind `PermissionDenied`. Defaults
+    /// to `None`.
+    pub fn set_address_filter(&mut self, filter: Option<Box<AddressFilter>>) {
+        self.address_filter = filter.map(Arc::from);
+    }
+
+    /// Returns whether connections to private addresses are blocked.
+    pub fn block_private_addresses(&self) -> bool {
+        self.block_private_addresses
+    }
+
+    /// Sets whether connections to private addresses are blocked.
+    ///
+    /// If enabled, resolved addresses in private ranges are never connected
+    /// to. These are the RFC 1918 ranges `10.0.0.0/8`, `172.16.0.0/12`, and
+    /// `192.168.0.0/16`, the loopback ranges `127.0.0.0/8` and `::1`, the
+    /// link-local ranges `169.254.0.0/16` and `fe80::/10`, the unique local
+    /// range `fc00::/7`, and the unspecified addresses `0.0.0.0/8` and `::`,
+    /// which connect to the local host. IPv4-mapped IPv6 addresses are checked
+    /// as the IPv4 address they map to. This protects against server-side
+    /// request forgery when the host being connected to is not trusted.
+    ///
+    /// The check is made after resolution, so it can't be bypassed by a host
+    /// whose DNS records point at a private address, including one which
+    /// resolves differently each time. Like the address filter, it applies to
+    /// the addresses of proxies and overridden hosts, but not to addresses
+    /// passed directly to `connect_addr` or `connect_addrs`. When connecting
+    /// through a proxy the target host is resolved by the proxy, so it can't
+    /// be checked.
+    ///
+    /// Private addresses of a host are skipped, and if every address is
+    /// private, connect calls fail with a `ConnectError::BlockedAddress`
+    /// error. Defaults to `false`.
+    pub fn set_block_private_addresses(&mut self, block_private_addresses: bool) {
+        self.block_private_addresses = block_private_addresses;
+    }
+
+    /// Returns the time resolved addresses are cached for.
+    pub fn dns_cache_ttl(&self) -> Option<Duration> {
+        self.dns_cache_ttl
+    }
+
+    /// Sets the time resolved addresses are cached for.
+    ///
+    /// If set, the addresses a host resolves to are reused by connect calls
+    /// made within the TTL rather than being resolved again. The cache is
+    /// shared between clones of the connector. Defaults to `None`, which
+    /// disables caching.
+    pub fn set_dns_cache_ttl(&mut self, ttl: Option<Duration>) {
+        self.dns_cache_ttl = ttl;
+    }
+
+    /// Returns the IP type-of-service value set on new connections.
+    pub fn tos(&self) -> Option<u32> {
+        self.tos
+    }
+
+    /// Sets the IP type-of-service value of packets sent over new connections.
+    ///
+    /// This sets `IP_TOS` for IPv4 connections and `IPV6_TCLASS` for IPv6
+    /// connections, and can be used to apply DSCP markings. Whether the value
+    /// has any effect depends on the operating system and the network. Defaults
+    /// to `None`, which leaves the operating system's default in place.
+    pub fn set_tos(&mut self, tos: Option<u32>) {
+        self.tos = tos;
+    }
+
+    /// Returns the priority set on new connections.
+    pub fn priority(&self) -> Option<u32> {
+        self.priority
+    }
+
+    /// Sets the priority of packets sent over new connections.
+    ///
+    /// This sets `SO_PRIORITY`, which is used by the Linux traffic control
+    /// subsystem to select a queue for each packet. Priorities outside of the
+    /// range 0 to 6 require the `CAP_NET_ADMIN` capability. It is only
+    /// supported on Linux, and connect calls fail with an `Unsupported` error
+    /// on other platforms if it is set. Defaults to `None`.
+    pub fn set_priority(&mut self, priority: Option<u32>) {
+        self.priority = priority;
+    }
+
+    /// Returns whether TCP Fast Open is enabled for new connections.
+    pub fn tcp_fastopen(&self) -> bool {
+        self.tcp_fastopen
+    }
+
+    /// Enables or disables TCP Fast Open for new connections.
+    ///
+    /// This sets `TCP_FASTOPEN_CONNECT`, which defers the TCP handshake until
+    /// data is first written so that the data can be sent with the SYN when
+    /// the remote host has previously provided a Fast Open cookie. It only
+    /// saves a round trip when data is written immediately after connecting,
+    /// which is the case for Hyper, as the request is written as soon as the
+    /// connection is established. Since the handshake is deferred, the
+    /// connection timeout does not apply, and errors such as a refused
+    /// connection are reported by the first write rather than by the connect
+    /// call. It is only supported on Linux, and connect calls fail with an
+    /// `Unsupported` error on other platforms if it is enabled. Defaults to
+    /// `false`.
+    pub fn set_tcp_fastopen(&mut self, tcp_fastopen: bool) {
+        self.tcp_fastopen = tcp_fastopen;
+    }
+
+    /// Returns the TCP user timeout of new connections.
+    pub fn user_timeout(&self) -> Option<Duration> {
+        self.user_timeout
+    }
+
+    /// Sets the TCP user timeout of new connections.
+    ///
+    /// This sets `TCP_USER_TIMEOUT`, which causes the kernel to close a
+    /// connection if transmitted data remains unacknowledged for longer than
+    /// the timeout. This detects dead peers of connections with outstanding
+    /// data much faster than keepalive. It is only supported on Linux, and
+    /// connect calls fail with an `Unsupported` error on other platforms if it
+    /// is set. Defaults to `None`.
+    pub fn set_user_timeout(&mut self, timeout: Option<Duration>) {
+        self.user_timeout = timeout;
+    }
+
+    /// Returns the TCP maximum segment size requested for new connections.
+    pub fn mss(&self) -> Option<u32> {
+        self.mss
+    }
+
+    /// Sets the TCP maximum segment size requested for new connections.
+    ///
+    /// This sets `TCP_MAXSEG` before connecting, which limits the size of the
+    /// segments sent over the connection. The value is an upper bound: the
+    /// kernel may lower it based on the path MTU and the segment size
+    /// advertised by the peer, and rejects values outside of a permitted
+    /// range with an `InvalidInput` error. It is only supported on Linux,
+    /// Android, macOS, iOS, and the BSDs, and connect calls fail with an
+    /// `Unsupported` error on other platforms if it is set. Defaults to
+    /// `None`.
+    pub fn set_mss(&mut self, mss: Option<u32>) {
+        self.mss = mss;
+    }
+
+    /// Returns the TCP congestion control algorithm used by new connections.
+    pub fn congestion_control(&self) -> Option<&str> {
+        self.congestion_control.as_deref()
+    }
+
+    /// Sets the TCP congestion control algorithm used by new connections,
+    /// such as `bbr`.
+    ///
+    /// This sets `TCP_CONGESTION`. Connect calls fail with an `InvalidInput`
+    /// error if the algorithm is not available in the kernel, and using an
+    /// algorithm not listed in `net.ipv4.tcp_allowed_congestion_control`
+    /// requires elevated privileges. It is only supported on Linux, and
+    /// connect calls fail with an `Unsupported` error on other platforms if it
+    /// is set. Defaults to `None`, which uses the system default algorithm.
+    pub fn set_congestion_control(&mut self, algorithm: Option<String>) {
+        self.congestion_control = algorithm;
+    }
+
+    /// Returns the send buffer size requested for new connections.
+    pub fn send_buffer_size(&self) -> Option<usize> {
+        self.send_buffer_size
+    }
+
+    /// Sets the send buffer size requested for new connections.
+    ///
+    /// This sets `SO_SNDBUF`. The operating system may clamp the value to a
+    /// permitted range or adjust it (Linux doubles it, for example). Defaults
+    /// to `None`, which leaves the operating system's automatic tuning in
+    /// place.
+    pub fn set_send_buffer_size(&mut self, size: Option<usize>) {
+        self.send_buffer_size = size;
+    }
+
+    /// Returns the receive buffer size requested for new connections.
+    pub fn recv_buffer_size(&self) -> Option<usize> {
+        self.recv_buffer_size
+    }
+
+    /// Sets the receive buffer size requested for new connections.
+    ///
+    /// This sets `SO_RCVBUF`. The operating system may clamp the value to a
+    /// permitted range or adjust it (Linux doubles it, for example). Defaults
+    /// to `None`, which leaves the operating system's automatic tuning in
+    /// place.
+    pub fn set_recv_buffer_size(&mut self, size: Option<usize>) {
+        self.recv_buffer_size = size;
+    }
+
+    /// Returns the `SO_LINGER` configuration of new connections.
+    pub fn linger(&self) -> Option<Option<Duration>> {
+        self.linger
+    }
+
+    /// Sets the `SO_LINGER` configuration of new connections.
+    ///
+    /// The outer `Option` controls whether the option is set at all:
+    ///
+    /// * `None` leaves the operating system's default in place. This is the
+    ///   default.
+    /// * `Some(None)` disables lingering, so closing the connection returns
+    ///   immediately and unsent data is delivered in the background.
+    /// * `Some(Some(duration))` enables lingering, so closing the connection
+    ///   blocks for up to `duration` while unsent data is delivered. A zero
+    ///   duration causes the connection to be aborted with a `RST` when it
+    ///   is closed rather than shut down gracefully.
+    pub fn set_linger(&mut self, linger: Option<Option<Duration>>) {
+        self.linger = linger;
+    }
+
+    /// Returns the maximum number of connect calls which may be in progress
+    /// at once.
+    pub fn max_concurrent_connects(&self) -> Option<usize> {
+        self.max_concurrent_connects
+    }
+
+    /// Sets the maximum number of connect calls which may be in progress at
+    /// once.
+    ///
+    /// If set, connect calls made while the limit is reached wait for an
+    /// earlier call to finish before starting. The wait is bounded by the
+    /// total timeout and the connect queue timeout, and connect calls fail
+    /// with a `TimedOut` error if either elapses first. The limit is shared
+    /// between clones of the connector, which protects the process from
+    /// exhausting threads or file descriptors when many connections to an
+    /// unresponsive host are made at once. Defaults to `None`.
+    pub fn set_max_concurrent_connects(&mut self, max: Option<usize>) {
+        self.max_concurrent_connects = max.map(|max| cmp::max(max, 1));
+    }
+
+    /// Returns the maximum time a connect call waits for other connect calls
+    /// to finish when the concurrent connect limit is reached.
+    pub fn connect_queue_timeout(&self) -> Option<Duration> {
+        self.connect_queue_timeout
+    }
+
+    /// Sets the maximum time a connect call waits for other connect calls to
+    /// finish when the concurrent connect limit is reached.
+    ///
+    /// A timeout of zero causes connect calls to fail immediately rather than
+    /// wait. Defaults to `None`, in which case connect calls wait until the
+    /// total timeout elapses, if one is set.
+    pub fn set_connect_queue_timeout(&mut self, timeout: Option<Duration>) {
+        self.connect_queue_timeout = timeout;
+    }
+
+    /// Returns the number of times a failed connect call is retried.
+    pub fn retries(&self) -> usize {
+        self.retries
+    }
+
+    /// Sets the number of times a failed connect call is retried.
+    ///
+    /// If every address associated with a host fails with an error that may
+    /// be transient (a refused, reset, or aborted connection, or a timeout),
+    /// the connector waits for the retry backoff and then tries all of the
+    /// addresses again. Hostname resolution is not retried, and retries do not
+    /// extend past the total timeout. Defaults to 0.
+    pub fn set_retries(&mut self, retries: usize) {
+        self.retries = retries;
+    }
+
+    /// Returns the number of times a failed attempt to connect to an address
+    /// is retried before moving on to the next address.
+    pub fn per_address_retries(&self) -> usize {
+        self.per_address_retries
+    }
+
+    /// Sets the number of times a failed attempt to connect to an address is
+    /// retried before moving on to the next address.
+    ///
+    /// If an attempt fails with an error that may be transient, the same
+    /// address is immediately tried again, with each retry bounded by the
+    /// connection timeout and the time left until the total timeout. This is
+    /// distinct from `set_retries`, which retries all of the addresses once
+    /// every one has failed. Only the final error for each address is
+    /// reported, but the connect failure hook is invoked for every attempt.
+    ///
+    /// Retries are only made when addresses are tried one at a time, not
+    /// with Happy Eyeballs or parallel attempts. Defaults to 0.
+    pub fn set_per_address_retries(&mut self, retries: usize) {
+        self.per_address_retries = retries;
+    }
+
+    /// Returns the time waited between retries.
+    pub fn retry_backoff(&self) -> Duration {
+        self.retry_backoff
+    }
+
+    /// Sets the time waited between retries.
+    ///
+    /// Defaults to 100 milliseconds.
+    pub fn set_retry_backoff(&mut self, backoff: Duration) {
+        self.retry_backoff = backoff;
+    }
+
+    /// Returns the fraction of the retry backoff by which it is randomly
+    /// varied.
+    pub fn retry_jitter(&self) -> f64 {
+        self.retry_jitter
+    }
+
+    /// Sets the fraction of the retry backoff by which it is randomly varied.
+    ///
+    /// Each wait between retries is chosen uniformly from the retry backoff
+    /// plus or minus this fraction of it, so a jitter of 0.2 with the default
+    /// backoff waits between 80 and 120 milliseconds. This prevents many
+    /// clients from retrying in lockstep after an outage. The value is
+    /// clamped to between 0 and 1, and NaN disables jitter. Defaults to 0.
+    pub fn set_retry_jitter(&mut self, jitter: f64) {
+        self.retry_jitter = if jitter.is_nan() { 0.0 } else { jitter.clamp(0.0, 1.0) };
+    }
+
+    /// Returns whether errors setting optional socket options are ignored.
+    pub fn ignore_option_errors(&self) -> bool {
+        self.ignore_option_errors
+    }
+
+    /// Sets whether errors setting optional socket options are ignored.
+    ///
+    /// If enabled, a failure to set one of the options which only tune a
+    /// connection is logged at the debug level and the connection attempt
+    /// continues without that option, rather than failing. This covers
+    /// `TCP_NODELAY`, keepalive, the TTL, type of service, priority, user
+    /// timeout, TCP Fast Open, maximum segment size, congestion control,
+    /// linger, and buffer size options, and is useful when the same
+    /// configuration is used on platforms or kernels which don't all support
+    /// them.
+    ///
+    /// Errors creating, binding, and connecting the socket, as well as errors
+    /// setting options which affect how it is bound (the address and port
+    /// reuse, freebind, transparent, and bind device options) or returned by
+    /// the socket configurator, always fail the attempt. Defaults to `false`.
+    pub fn set_ignore_option_errors(&mut self, ignore_option_errors: bool) {
+        self.ignore_option_errors = ignore_option_errors;
+    }
+
+    /// Returns the callback used to configure new sockets, if one is set.
+    pub fn socket_configurator(&self) -> Option<&SocketConfigurator> {
+        self.socket_configurator.as_deref()
+    }
+
+    /// Sets a callback used to configure new sockets.
+    ///
+    /// The callback is invoked with each newly created `socket2::Socket`
+    /// after all other configured options have been applied and immediately
+    /// before it is connected, allowing arbitrary socket options to be set.
+    /// If the callback returns an error, the attempt to connect to that
+    /// address fails with it.
+    pub fn set_socket_configurator(&mut self, configurator: Option<Box<SocketConfigurator>>) {
+        self.socket_configurator = configurator.map(Arc::from);
+    }
+
+    /// Returns the callback invoked after each successful connection attempt, if one is set.
+    pub fn connect_success_hook(&self) -> Option<&SuccessHook> {
+        self.connect_success_hook.as_deref()
+    }
+
+    /// Sets a callback invoked after each successful connection attempt.
+    ///
+    /// The callback is passed the address connected to and the time taken to
+    /// establish the connection.
+    pub fn set_connect_success_hook(&mut self, hook: Option<Box<SuccessHook>>) {
+        self.connect_success_hook = hook.map(Arc::from);
+    }
+
+    /// Returns the callback invoked after each failed connection attempt, if one is set.
+    pub fn connect_failure_hook(&self) -> Option<&FailureHook> {
+        self.connect_failure_hook.as_deref()
+    }
+
+    /// Sets a callback invoked after each failed connection attempt.
+    ///
+    /// The callback is passed the address that could not be connected to and
+    /// the error encountered. It is invoked once for each address that fails,
+    /// even if a later address succeeds.
+    pub fn set_connect_failure_hook(&mut self, hook: Option<Box<FailureHook>>) {
+        self.connect_failure_hook = hook.map(Arc::from);
+    }
+
+    /// Returns the callback invoked after a host is resolved, if one is set.
+    pub fn resolve_hook(&self) -> Option<&ResolveHook> {
+        self.resolve_hook.as_deref()
+    }
+
+    /// Sets a callback invoked after a host is resolved during a connect call.
+    ///
+    /// The callback is passed the host and the addresses it resolved to, in
+    /// the order they will be tried, before the address filter is applied.
+    /// It is invoked even if the addresses were taken from the DNS cache or
+    /// the host resolved to no addresses, but not if resolution failed. When
+    /// connecting through a proxy, it is invoked for the proxy's host.
+    pub fn set_resolve_hook(&mut self, hook: Option<Box<ResolveHook>>) {
+        self.resolve_hook = hook.map(Arc::from);
+    }
+
+    /// Returns the proxy configuration.
+    pub fn proxy(&self) -> Option<&ProxyConfig> {
+        self.proxy.as_ref()
+    }
+
+    /// Sets the proxy connections are tunneled through.
+    ///
+    /// If set, the connector connects to the proxy rather than the requested
+    /// host, subject to all of the connector's configuration, and then asks
+    /// the proxy to establish a tunnel to the requested host. Defaults to
+    /// `None`.
+    pub fn set_proxy(&mut self, proxy: Option<ProxyConfig>) {
+        self.proxy = proxy;
+    }
+
+    /// Returns the hosts which are connected to directly rather than through
+    /// the proxy.
+    pub fn proxy_bypass(&self) -> &[String] {
+        &self.proxy_bypass
+    }
+
+    /// Sets the hosts which are connected to directly rather than through the
+    /// proxy.
+    ///
+    /// This follows the conventions of the `NO_PROXY` environment variable.
+    /// Each entry is either a domain such as `example.com`, which matches the
+    /// domain and all of its subdomains, an IP address or CIDR block such as
+    /// `10.0.0.0/8`, which matches hosts given as IP addresses within it, or
+    /// `*`, which matches every host. Domains are matched case-insensitively,
+    /// and a leading `.` or `*.` is ignored. Defaults to no hosts.
+    pub fn set_proxy_bypass(&mut self, bypass: Vec<String>) {
+        self.proxy_bypass = bypass;
+    }
+
+    /// Returns the version of the PROXY protocol header sent on new
+    /// connections.
+    pub fn proxy_protocol(&self) -> Option<ProxyProtocolVersion> {
+        self.proxy_protocol
+    }
+
+    /// Sets the version of the PROXY protocol header sent on new connections.
+    ///
+    /// If set, a PROXY protocol header is written to each connection as soon
+    /// as it is established, before it is used by Hyper, for use with load
+    /// balancers which expect one. The source and destination addresses in
+    /// the header are the local and peer addresses of the connection. When
+    /// connecting through a proxy configured with `set_proxy`, the header is
+    /// sent to the proxy. Defaults to `None`.
+    pub fn set_proxy_protocol(&mut self, version: Option<ProxyProtocolVersion>) {
+        self.proxy_protocol = version;
+    }
+
+    /// Returns the hosts connected to in place of the requested host.
+    pub fn hosts(&self) -> &[(String, u16)] {
+        &self.hosts
+    }
+
+    /// Sets the hosts connected to in place of the requested host.
+    ///
+    /// If any hosts are configured, the host and port passed to each connect
+    /// call are ignored, and the configured hosts are used in turn instead,
+    /// with each call starting with the host after the one the previous call
+    /// started with. If connecting to a host fails, the next one is tried,
+    /// until every host has been tried or the total timeout elapses. The
+    /// rotation is shared between clones of the connector. This is intended
+    /// as a simple form of client-side load balancing between equivalent
+    /// hosts. Defaults to no hosts.
+    pub fn set_hosts(&mut self, hosts: Vec<(String, u16)>) {
+        self.hosts = hosts;
+    }
+
+    /// Returns the host and port connected to if connecting to the requested
+    /// host fails.
+    pub fn fallback_host(&self) -> Option<(&str, u16)> {
+        self.fallback_host.as_ref().map(|(host, port)| (&**host, *port))
+    }
+
+    /// Sets the host and port connected to if connecting to the requested
+    /// host fails.
+    ///
+    /// If every attempt to connect to the requested host fails, including
+    /// any retries, the fallback host is resolved and connected to with the
+    /// same configuration, and the result of that is returned instead. The
+    /// same applies if the requested host can't be resolved or has no
+    /// addresses. Other errors, such as an invalid host, blocked or filtered
+    /// addresses, or the total timeout elapsing, are returned without trying
+    /// the fallback host. The connection timeout applies to the addresses of
+    /// each host separately, while the total timeout covers both. Defaults to
+    /// `None`.
+    pub fn set_fallback_host(&mut self, fallback: Option<(String, u16)>) {
+        self.fallback_host = fallback;
+    }
+
+    /// Returns the maximum number of addresses tried per connect call.
+    pub fn max_attempts(&self) -> Option<usize> {
+        self.max_attempts
+    }
+
+    /// Sets the maximum number of addresses tried per connect call.
+    ///
+    /// The limit applies to the addresses which would actually be attempted,
+    /// after blocked and filtered addresses are removed and the remainder is
+    /// ordered, including by address cooldown and sticky addresses. Addresses
+    /// beyond the limit are ignored. Defaults to `None`, in which case every
+    /// address associated with the host is tried.
+    pub fn set_max_attempts(&mut self, max_attempts: Option<usize>) {
+        self.max_attempts = max_attempts;
+    }
+
+    /// Returns whether resolved addresses are shuffled.
+    pub fn shuffle_addresses(&self) -> bool {
+        self.shuffle_addresses
+    }
+
+    /// Sets whether resolved addresses are shuffled.
+    ///
+    /// If enabled, the addresses associated with a host are randomly
+    /// reordered on each connect call, spreading connections across them.
+    /// Any address family preference is applied after shuffling. Defaults to
+    /// `false`.
+    pub fn set_shuffle_addresses(&mut self, shuffle_addresses: bool) {
+        self.shuffle_addresses = shuffle_addresses;
+    }
+
+    /// Returns the time for which an address is deprioritized after a
+    /// connection attempt to it fails.
+    pub fn address_cooldown(&self) -> Option<Duration> {
+        self.address_cooldown
+    }
+
+    /// Sets the time for which an address is deprioritized after a
+    /// connection attempt to it fails.
+    ///
+    /// If set, addresses which failed within the cooldown are moved to the
+    /// end of a host's addresses, so that healthy addresses are tried first.
+    /// They are still tried if every other address fails. A successful
+    /// connection to an address ends its cooldown. Failures are shared
+    /// between clones of the connector. Defaults to `None`.
+    pub fn set_address_cooldown(&mut self, cooldown: Option<Duration>) {
+        self.address_cooldown = cooldown;
+    }
+
+    /// Returns whether the address last connected to for a host is tried
+    /// first.
+    pub fn sticky_addresses(&self) -> bool {
+        self.sticky_addresses
+    }
+
+    /// Sets whether the address last connected to for a host is tried first.
+    ///
+    /// If enabled, the connector remembers the address each successful
+    /// connect call connected to, and moves it to the front of the host's
+    /// addresses on later calls, after all other ordering has been applied.
+    /// The address is forgotten if a connect call to the host fails. The
+    /// remembered addresses are shared between clones of the connector.
+    /// Defaults to `false`.
+    pub fn set_sticky_addresses(&mut self, sticky_addresses: bool) {
+        self.sticky_addresses = sticky_addresses;
+    }
+
+    /// Returns the URL schemes accepted by connect calls.
+    pub fn allowed_schemes(&self) -> &[String] {
+        &self.allowed_schemes
+    }
+
+    /// Sets the URL schemes accepted by connect calls.
+    ///
+    /// Connect calls for any other scheme fail with an `InvalidInput` error.
+    /// This is only checked if strict scheme checking is enabled. Defaults to
+    /// `["http"]`.
+    pub fn set_allowed_schemes(&mut self, schemes: Vec<String>) {
+        self.allowed_schemes = schemes;
+    }
+
+    /// Returns whether the scheme of connect calls is checked.
+    pub fn strict_scheme(&self) -> bool {
+        self.strict_scheme
+    }
+
+    /// Sets whether the scheme of connect calls is checked.
+    ///
+    /// If disabled, a plain TCP connection is made for any scheme, which
+    /// allows the connector to be used for protocols other than HTTP.
+    /// Defaults to `true`.
+    pub fn set_strict_scheme(&mut self, strict_scheme: bool) {
+        self.strict_scheme = strict_scheme;
+    }
+
+    /// Returns a snapshot of counters of the connect calls made by the
+    /// connector.
+    ///
+    /// The counters are shared between clones of the connector, and cover
+    /// every connect call, including connections to a single address made by
+    /// `connect_addr`.
+    pub fn stats(&self) -> ConnectorStats {
+        self.stats.snapshot()
+    }
+
+    /// Returns percentiles of the time taken by successful connect calls.
+    ///
+    /// The time of a connect call includes hostname resolution, every
+    /// connection attempt made, and any proxy handshake. It is recorded in a
+    /// fixed-size histogram which is shared between clones of the connector.
+    pub fn latency_percentiles(&self) -> LatencyStats {
+        self.stats.latency()
+    }
+
+    /// Clears the state the connector has built up about hosts and addresses.
+    ///
+    /// This empties the DNS cache, forgets the addresses remembered by the
+    /// sticky addresses option, and ends the cooldown of any recently failed
+    /// addresses, so that later connect calls resolve hosts afresh. The state
+    /// is shared between clones of the connector, so they are reset as well.
+    /// Connect calls in progress are unaffected, but may record new state
+    /// when they complete. Statistics are not reset.
+    pub fn reset(&self) {
+        self.dns_cache.clear();
+        self.sticky_addrs.clear();
+        self.failed_addrs.clear();
+    }
+
+    /// Returns the addresses that a connect call would try for a host.
+    ///
+    /// This performs the same resolution, filtering, and ordering as a
+    /// connect call, including use of the DNS cache and custom resolver, but
+    /// does not make any connection attempts. If address shuffling is enabled
+    /// the order will differ from call to call.
+    ///
+    /// No sockets are created, so this can be used to check the connector's
+    /// address selection where outbound connections are blocked. With a
+    /// custom resolver, no network access is needed at all.
+    pub fn resolve(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        let (addrs, _) = self.resolve_until(host, port, self.deadline())?;
+        self.filter_addrs(host, addrs).map_err(io::Error::from)
+    }
+
+    /// Resolves hosts ahead of time, populating the DNS cache.
+    ///
+    /// Each host is resolved in turn, bounded by the resolve timeout and
+    /// total timeout, so that later connect calls to it can use the cached
+    /// addresses. Hosts which are already cached are not resolved again. The
+    /// result for each host is returned in order, with a host which resolves
+    /// to no addresses reported as a `ConnectError::NoAddresses` error.
+    ///
+    /// The hosts are resolved as given, so when connecting through a proxy it
+    /// is the proxy's host which should be warmed. The hosts are still
+    /// resolved if the DNS cache is disabled, which checks that they can be,
+    /// but nothing is cached.
+    pub fn prewarm(&self, hosts: &[(&str, u16)]) -> Vec<io::Result<()>> {
+        hosts.iter()
+            .map(|&(host, port)| {
+                let (addrs, _) = self.lookup(host, port, self.deadline())?;
+                if addrs.is_empty() {
+                    return Err(ConnectError::no_addresses(host).into());
+                }
+                Ok(())
+            })
+            .collect()
+    }
+
+    /// Resolves and orders the addresses of a host, also returning whether
+    /// they were taken from the DNS cache.
+    fn resolve_until(&self,
+                     host: &str,
+                     port: u16,
+                     deadline: Option<Instant>)
+                     -> io::Result<(Vec<SocketAddr>, bool)> {
+        let (addrs, cached) = self.lookup(host, port, deadline)?;
+        let mut addrs = self.order_addrs(addrs);
+        if self.address_cooldown.is_some() {
+            addrs.sort_by_key(|a| self.failed_addrs.is_cooling_down(a));
+        }
+        if self.sticky_addresses {
+            if let Some(sticky) = self.sticky_addrs.get(host, port) {
+                if let Some(i) = addrs.iter().position(|a| *a == sticky) {
+                    addrs[..=i].rotate_right(1);
+                }
+            }
+        }
+        Ok((addrs, cached))
+    }
+
+    fn lookup(&self,
+              host: &str,
+              port: u16,
+              deadline: Option<Instant>)
+              -> io::Result<(Vec<SocketAddr>, bool)> {
+        if let Some(addrs) = self.host_override(host) {
+            let addrs = addrs.iter().map(|&ip| SocketAddr::new(ip, port)).collect();
+            return Ok((addrs, false));
+        }
+
+        let ttl = match self.dns_cache_ttl {
+            Some(ttl) => ttl,
+            None => return self.lookup_uncached(host, port, deadline).map(|a| (a, false)),
+        };
+
+        if let Some(addrs) = self.dns_cache.get(host, port) {
+            self.stats.record_dns_cache(true);
+            return Ok((addrs, true));
+        }
+
+        self.stats.record_dns_cache(false);
+        let addrs = self.lookup_uncached(host, port, deadline)?;
+        self.dns_cache.insert(host, port, addrs.clone(), Instant::now() + ttl);
+        Ok((addrs, false))
+    }
+
+    fn lookup_uncached(&self,
+                       host: &str,
+                       port: u16,
+                       deadline: Option<Instant>)
+                       -> io::Result<Vec<SocketAddr>> {
+        let timeout = match remaining(self.resolve_timeout, deadline)? {
+            Some(timeout) => timeout,
+            None => return resolve_with(self.resolver.as_deref(), host, port),
+        };
+
+        let (tx, rx) = mpsc::channel();
+        let host = host.to_owned();
+        let resolver = self.resolver.clone();
+        thread::spawn(move || {
+            let addrs = resolve_with(resolver.as_deref(), &host, port);
+            let _ = tx.send(addrs);
+        });
+
+        match rx.recv_timeout(timeout) {
+            Ok(addrs) => addrs,
+            Err(RecvTimeoutError::Timeout) => {
+                Err(io::Error::new(io::ErrorKind::TimedOut, "hostname resolution timed out"))
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                Err(io::Error::new(io::ErrorKind::Other, "hostname resolution failed"))
+            }
+        }
+    }
+
+    /// Removes blocked private addresses and the addresses rejected by the
+    /// address filter, failing if they all are, and then limits the rest to
+    /// the maximum number of attempts.
+    fn filter_addrs(&self,
+                    host: &str,
+                    mut addrs: Vec<SocketAddr>)
+                    -> Result<Vec<SocketAddr>, ConnectError> {
+        if self.block_private_addresses && !addrs.is_empty() {
+            addrs.retain(|a| !is_private(a.ip()));
+            if addrs.is_empty() {
+                let err = io::Error::new(io::ErrorKind::PermissionDenied,
+                                         format!("all addresses of host `{}` are private, and \
+                                                  connections to them are blocked",
+                                                 host));
+                return Err(ConnectError::BlockedAddress(err));
+            }
+        }
+
+        if let Some(ref filter) = self.address_filter {
+            if !addrs.is_empty() {
+                addrs.retain(|a| filter(a));
+                if addrs.is_empty() {
+                    let err = io::Error::new(io::ErrorKind::PermissionDenied,
+                                             format!("all addresses of host `{}` were rejected \
+                                                      by the address filter",
+                                                     host));
+                    return Err(ConnectError::NoAddresses(err));
+                }
+            }
+        }
+
+        if let Some(max_attempts) = self.max_attempts {
+            addrs.truncate(max_attempts);
+        }
+        Ok(addrs)
+    }
+
+    fn order_addrs(&self, mut addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+        // some resolvers return duplicates, which would otherwise each be tried
+        let mut seen = HashSet::new();
+        addrs.retain(|a| seen.insert(*a));
+
+        if self.disable_ipv6 {
+            addrs.retain(|a| a.is_ipv4());
+        }
+        if self.shuffle_addresses {
+            rng::shuffle(&mut addrs);
+        }
+        match self.address_family {
+            AddressFamily::Any => {}
+            AddressFamily::Ipv4Only => addrs.retain(|a| a.is_ipv4()),
+            AddressFamily::Ipv6Only => addrs.retain(|a| a.is_ipv6()),
+            AddressFamily::PreferIpv4 => addrs.sort_by_key(|a| a.is_ipv6()),
+            AddressFamily::PreferIpv6 => addrs.sort_by_key(|a| a.is_ipv4()),
+        }
+        if let Some(ref sort) = self.address_sort {
+            addrs.sort_by(|a, b| sort(a, b));
+        }
+
+        addrs
+    }
+
+    fn socket(&self, addr: &SocketAddr) -> io::Result<Socket> {
+        let domain = match *addr {
+            SocketAddr::V4(_) => Domain::ipv4(),
+            SocketAddr::V6(_) => Domain::ipv6(),
+        };
+        let socket = Socket::new(domain, Type::stream(), None)?;
+        if self.nodelay {
+            self.set_option("TCP_NODELAY", socket.set_nodelay(true))?;
+        }
+        if let Some(keepalive) = self.keepalive {
+            self.set_option("SO_KEEPALIVE", sys::set_keepalive(&socket, keepalive))?;
+        }
+        if let Some(ttl) = self.ttl {
+            match *addr {
+                SocketAddr::V4(_) => self.set_option("IP_TTL", socket.set_ttl(ttl))?,
+                SocketAddr::V6(_) => {
+                    self.set_option("IPV6_UNICAST_HOPS", sys::set_unicast_hops_v6(&socket, ttl))?
+                }
+            }
+        }
+        if let Some(tos) = self.tos {
+            match *addr {
+                SocketAddr::V4(_) => self.set_option("IP_TOS", sys::set_tos(&socket, tos))?,
+                SocketAddr::V6(_) => {
+                    self.set_option("IPV6_TCLASS", sys::set_tclass_v6(&socket, tos))?
+                }
+            }
+        }
+        if let Some(priority) = self.priority {
+            self.set_option("SO_PRIORITY", sys::set_priority(&socket, priority))?;
+        }
+        if let Some(timeout) = self.user_timeout {
+            self.set_option("TCP_USER_TIMEOUT", sys::set_user_timeout(&socket, timeout))?;
+        }
+        if self.tcp_fastopen {
+            self.set_option("TCP_FASTOPEN_CONNECT", sys::set_fastopen_connect(&socket))?;
+        }
+        if let Some(mss) = self.mss {
+            self.set_option("TCP_MAXSEG", sys::set_mss(&socket, mss))?;
+        }
+        if let Some(ref algorithm) = self.congestion_control {
+            self.set_option("TCP_CONGESTION", sys::set_congestion_control(&socket, algorithm))?;
+        }
+        if let Some(linger) = self.linger {
+            self.set_option("SO_LINGER", socket.set_linger(linger))?;
+        }
+        if let Some(size) = self.send_buffer_size {
+            self.set_option("SO_SNDBUF", socket.set_send_buffer_size(size))?;
+        }
+        if let Some(size) = self.recv_buffer_size {
+            self.set_option("SO_RCVBUF", socket.set_recv_buffer_size(size))?;
+        }
+        if self.reuse_address {
+            socket.set_reuse_address(true)?;
+        }
+        if self.reuse_port {
+            sys::set_reuse_port(&socket)?;
+        }
+        if self.freebind {
+            sys::set_freebind(&socket)?;
+        }
+        if self.transparent {
+            match *addr {
+                SocketAddr::V4(_) => sys::set_transparent(&socket)?,
+                SocketAddr::V6(_) => sys::set_transparent_v6(&socket)?,
+            }
+        }
+        if let Some(local) = self.local_address {
+            if local.is_ipv4() != addr.is_ipv4() {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                          "local address family does not match remote address"));
+            }
+        }
+        let local = match (self.local_address, *addr) {
+            (Some(local), _) => Some(local),
+            (None, _) if self.local_port_range.is_none() => None,
+            (None, SocketAddr::V4(_)) => Some((Ipv4Addr::UNSPECIFIED, 0).into()),
+            (None, SocketAddr::V6(_)) => Some((Ipv6Addr::UNSPECIFIED, 0).into()),
+        };
+        if let Some(local) = local {
+            let local = local_bind_addr(local, addr);
+            match self.local_port_range {
+                Some(range) if local.port() == 0 => bind_in_range(&socket, local, range)?,
+                _ => socket.bind(&SockAddr::from(local))?,
+            }
+        }
+        if let Some(ref device) = self.bind_device {
+            sys::set_bind_device(&socket, device)?;
+        }
+        if let Some(ref configurator) = self.socket_configurator {
+            configurator(&socket)?;
+        }
+        Ok(socket)
+    }
+
+    /// Handles the result of setting a socket option which the connection can
+    /// be made without.
+    fn set_option(&self, name: &str, r: io::Result<()>) -> io::Result<()> {
+        match r {
+            Err(ref e) if self.ignore_option_errors => {
+                debug!("ignoring error setting {}: {}", name, e);
+                Ok(())
+            }
+            r => r,
+        }
+    }
+
+    /// Configures a newly connected socket.
+    fn finish(&self, socket: Socket) -> io::Result<TcpStream> {
+        let stream = TcpStream::from(socket);
+        self.set_stream_timeouts(&stream)?;
+        if let Some(version) = self.proxy_protocol {
+            let header = proxy_protocol::header(version, stream.local_addr()?, stream.peer_addr()?);
+            (&stream).write_all(&header)?;
+        }
+        Ok(stream)
+    }
+
+    fn connect_once(&self, addr: SocketAddr, timeout: Option<Duration>) -> io::Result<TcpStream> {
+        let socket = self.socket(&addr)?;
+        connect_socket(&socket, addr, timeout, self.timeout_strategy)?;
+        self.finish(socket)
+    }
+
+    /// Connects to a host, finishing before the specified deadline.
+    ///
+    /// This behaves like a normal connect call, except that the entire
+    /// process is bounded by the deadline in addition to the total timeout,
+    /// if one is configured. This is useful when the time available to
+    /// connect is only known at the time of the call.
+    ///
+    /// No URL scheme is given, so the allowed schemes are not checked and the
+    /// connection timeout is used rather than any timeout set for a scheme.
+    pub fn connect_with_deadline(&self,
+                                 host: &str,
+                                 port: u16,
+                                 deadline: Instant)
+                                 -> io::Result<HttpStream> {
+        let deadline = match self.deadline() {
+            Some(total) if total < deadline => total,
+            _ => deadline,
+        };
+
+        self.connect_host(host, port, self.connect_timeout, Some(deadline))
+            .map(|c| HttpStream(c.stream))
+            .map_err(io::Error::from)
+    }
+
+    /// Connects to a host, also returning the time taken by the successful
+    /// connection attempt.
+    ///
+    /// The duration covers only the attempt that succeeded, and does not
+    /// include hostname resolution, failed attempts to other addresses, or
+    /// retries. When connecting through a proxy, it covers the connection to
+    /// the proxy and the proxy handshake.
+    pub fn connect_timed(&self,
+                         host: &str,
+                         port: u16,
+                         scheme: &str)
+                         -> io::Result<(HttpStream, Duration)> {
+        self.connect_scheme(host, port, scheme)
+            .map(|c| (HttpStream(c.stream), c.info.elapsed))
+    }
+
+    /// Connects to a host, also returning information about which address
+    /// the connection was established to and from.
+    ///
+    /// This is useful to detect degraded paths to a host, such as when IPv6
+    /// attempts regularly fail before an IPv4 address is successfully
+    /// connected to.
+    pub fn connect_with_info(&self,
+                             host: &str,
+                             port: u16,
+                             scheme: &str)
+                             -> io::Result<(HttpStream, ConnectInfo)> {
+        let Connected { stream, mut info } = self.connect_scheme(host, port, scheme)?;
+        info.local_addr = stream.local_addr().ok();
+        Ok((HttpStream(stream), info))
+    }
+
+    /// Connects to a host, also returning a breakdown of the time taken.
+    ///
+    /// The time spent resolving the host is reported separately from the
+    /// time spent connecting to it, along with the time taken by each
+    /// connection attempt, which shows whether DNS or the TCP handshake
+    /// dominates the latency of connect calls.
+    ///
+    /// Like `connect_with_deadline`, this takes no URL scheme, so scheme
+    /// restrictions and per-scheme connection timeouts don't apply.
+    pub fn connect_detailed(&self,
+                            host: &str,
+                            port: u16)
+                            -> io::Result<(HttpStream, ConnectTiming)> {
+        self.connect_host(host, port, self.connect_timeout, self.deadline())
+            .map(|c| (HttpStream(c.stream), c.info.timing))
+            .map_err(io::Error::from)
+    }
+
+    /// Connects to a host, returning the underlying socket.
+    ///
+    /// The socket is configured and connected exactly as it would be by a
+    /// normal connect call, but is returned as a `socket2::Socket` so that
+    /// further socket options can be applied to it. As no URL scheme is
+    /// given, scheme restrictions and per-scheme connection timeouts are
+    /// ignored.
+    pub fn connect_socket(&self, host: &str, port: u16) -> io::Result<Socket> {
+        self.connect_host(host, port, self.connect_timeout, self.deadline())
+            .map(|c| Socket::from(c.stream))
+            .map_err(io::Error::from)
+    }
+
+    /// Checks that a host is reachable, returning the time taken by the
+    /// successful connection attempt.
+    ///
+    /// The connection is made exactly as it would be by a normal connect
+    /// call, and is closed immediately. This is useful for health checks
+    /// which should not issue an HTTP request. There is no URL scheme, so the
+    /// connection timeout is used even if timeouts are set for schemes.
+    pub fn probe(&self, host: &str, port: u16) -> io::Result<Duration> {
+        self.connect_host(host, port, self.connect_timeout, self.deadline())
+            .map(|c| c.info.elapsed)
+            .map_err(io::Error::from)
+    }
+
+    /// Connects to a host on a new thread.
+    ///
+    /// The connect call runs on a clone of the connector, so it is not
+    /// affected by later changes to this connector's configuration, though it
+    /// does share the DNS cache. The total timeout, if any, starts when this
+    /// method is called. The result is returned by joining the handle.
+    ///
+    /// No URL scheme is given, so the allowed schemes are not checked and the
+    /// connection timeout is used rather than any timeout set for a scheme.
+    pub fn connect_spawn(&self, host: String, port: u16) -> JoinHandle<io::Result<HttpStream>> {
+        let connector = self.clone();
+        let deadline = self.deadline();
+        thread::spawn(move || {
+            connector.connect_host(&host, port, connector.connect_timeout, deadline)
+                .map(|c| HttpStream(c.stream))
+                .map_err(io::Error::from)
+        })
+    }
+
+    /// Connects to a host, giving up early if `cancel` is set.
+    ///
+    /// The connect call runs on a separate thread while the calling thread
+    /// polls the flag every 10 milliseconds. If the flag is set before the
+    /// connection is established, this returns an `Interrupted` error
+    /// immediately, and the connect call is abandoned in the background,
+    /// where it remains bounded by the configured timeouts.
+    ///
+    /// Like `connect_spawn`, this takes no URL scheme, so scheme restrictions
+    /// and per-scheme connection timeouts don't apply.
+    pub fn connect_cancellable(&self,
+                               host: &str,
+                               port: u16,
+                               cancel: Arc<AtomicBool>)
+                               -> io::Result<HttpStream> {
+        let interrupted = || io::Error::new(io::ErrorKind::Interrupted, "connect cancelled");
+        if cancel.load(AtomicOrdering::SeqCst) {
+            return Err(interrupted());
+        }
+
+        let (tx, rx) = mpsc::channel();
+        let connector = self.clone();
+        let host = host.to_owned();
+        let deadline = self.deadline();
+        thread::spawn(move || {
+            let r = connector.connect_host(&host, port, connector.connect_timeout, deadline);
+            let _ = tx.send(r);
+        });
+
+        let interval = Duration::from_millis(CANCEL_POLL_INTERVAL_MS);
+        loop {
+            match rx.recv_timeout(interval) {
+                Ok(r) => return r.map(|c| HttpStream(c.stream)).map_err(io::Error::from),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(io::Error::new(io::ErrorKind::Other, "connect failed"));
+                }
+            }
+            if cancel.load(AtomicOrdering::SeqCst) {
+                return Err(interrupted());
+            }
+        }
+    }
+
+    /// Connects to a host, using the provided connection timeout in place of
+    /// the configured one.
+    ///
+    /// This is useful when an occasional request justifies a different
+    /// connection timeout, and takes precedence over any timeout configured
+    /// for the scheme. The connector's configuration is not modified.
+    pub fn connect_with_timeout(&self,
+                                host: &str,
+                                port: u16,
+                                scheme: &str,
+                                timeout: Option<Duration>)
+                                -> hyper::Result<HttpStream> {
+        self.connect_scheme_with_timeout(host, port, scheme, timeout)
+            .map(|c| HttpStream(c.stream))
+            .map_err(From::from)
+    }
+
+    fn connect_scheme(&self, host: &str, port: u16, scheme: &str) -> io::Result<Connected> {
+        let timeout = self.connect_timeout_for_scheme(scheme);
+        self.connect_scheme_with_timeout(host, port, scheme, timeout)
+    }
+
+    fn connect_scheme_with_timeout(&self,
+                                   host: &str,
+                                   port: u16,
+                                   scheme: &str,
+                                   timeout: Option<Duration>)
+                                   -> io::Result<Connected> {
+        if self.strict_scheme && !self.allowed_schemes.iter().any(|s| s == scheme) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid scheme for http"));
+        }
+
+        self.connect_host(host, port, timeout, self.deadline()).map_err(io::Error::from)
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.total_timeout.map(|t| Instant::now() + t)
+    }
+
+    fn connect_host(&self,
+                    host: &str,
+                    port: u16,
+                    timeout: Option<Duration>,
+                    deadline: Option<Instant>)
+                    -> Result<Connected, ConnectError> {
+        let start = Instant::now();
+        let r = self.acquire_permit(deadline)
+            .and_then(|_permit| self.connect_with_fallback(host, port, timeout, deadline))
+            .map_err(|e| e.check_deadline(deadline));
+        self.complete(r, start)
+    }
+
+    /// Waits until the connect call may start, if the number of connect calls
+    /// in progress is limited.
+    fn acquire_permit(&self,
+                      deadline: Option<Instant>)
+                      -> Result<Option<limit::Permit>, ConnectError> {
+        let max = match self.max_concurrent_connects {
+            Some(max) => max,
+            None => return Ok(None),
+        };
+
+        let deadline = match (deadline, self.connect_queue_timeout.map(|t| Instant::now() + t)) {
+            (Some(a), Some(b)) => Some(cmp::min(a, b)),
+            (a, b) => a.or(b),
+        };
+        match limit::acquire(&self.in_flight, max, deadline) {
+            Some(permit) => Ok(Some(permit)),
+            None => {
+                let err = io::Error::new(io::ErrorKind::TimedOut,
+                                         "timed out waiting for other connect calls to finish");
+                Err(ConnectError::Timeout(err))
+            }
+        }
+    }
+
+    /// Prepares a connection to be handed off to the caller, and records the
+    /// result of the connect call.
+    fn complete(&self,
+                r: Result<Connected, ConnectError>,
+                start: Instant)
+                -> Result<Connected, ConnectError> {
+        let r = r.and_then(|mut connected| {
+            if self.nonblocking_stream {
+                connected.stream.set_nonblocking(true).map_err(ConnectError::Other)?;
+            }
+            connected.info.keepalive = self.keepalive;
+            connected.info.user_timeout = self.user_timeout;
+            Ok(connected)
+        });
+        self.stats.record(&r);
+        if r.is_ok() {
+            self.stats.record_latency(start.elapsed());
+        }
+        r
+    }
+
+    fn connect_with_fallback(&self,
+                             host: &str,
+                             port: u16,
+                             timeout: Option<Duration>,
+                             deadline: Option<Instant>)
+                             -> Result<Connected, ConnectError> {
+        let err = match self.connect_balanced(host, port, timeout, deadline) {
+            Ok(connected) => return Ok(connected),
+            Err(e) => e.check_deadline(deadline),
+        };
+
+        match self.fallback_host {
+            Some((ref fallback, fallback_port)) if err.is_unreachable() => {
+                warn!("failed to connect to {}:{}, trying fallback host {}:{}: {}",
+                      host,
+                      port,
+                      fallback,
+                      fallback_port,
+                      err);
+                self.connect_target(fallback, fallback_port, timeout, deadline)
+            }
+            _ => Err(err),
+        }
+    }
+
+    /// Connects to the requested host, or to the next of the configured
+    /// hosts if there are any.
+    fn connect_balanced(&self,
+                        host: &str,
+                        port: u16,
+                        timeout: Option<Duration>,
+                        deadline: Option<Instant>)
+                        -> Result<Connected, ConnectError> {
+        if self.hosts.is_empty() {
+            return self.connect_target(host, port, timeout, deadline);
+        }
+
+        let start = self.next_host.fetch_add(1, AtomicOrdering::Relaxed);
+        let mut i = 0;
+        loop {
+            let (ref host, port) = self.hosts[(start + i) % self.hosts.len()];
+            let err = match self.connect_target(host, port, timeout, deadline) {
+                Ok(connected) => return Ok(connected),
+                Err(e) => e,
+            };
+
+            i += 1;
+            if i == self.hosts.len() {
+                return Err(err);
+            }
+            warn!("failed to connect to {}:{}, trying next host: {}", host, port, err);
+        }
+    }
+
+    fn connect_target(&self,
+                      host: &str,
+                      port: u16,
+                      timeout: Option<Duration>,
+                      deadline: Option<Instant>)
+                      -> Result<Connected, ConnectError> {
+        validate_host(host).map_err(ConnectError::Other)?;
+
+        match self.proxy_for(host) {
+            Some(proxy) => proxy::connect(self, proxy, host, port, timeout, deadline),
+            None => self.connect_direct(host, port, timeout, deadline),
+        }
+    }
+
+    /// Returns the proxy used to connect to a host, if any.
+    fn proxy_for(&self, host: &str) -> Option<&ProxyConfig> {
+        self.proxy.as_ref().filter(|_| !proxy::bypassed(&self.proxy_bypass, host))
+    }
+
+    fn connect_direct(&self,
+                      host: &str,
+                      port: u16,
+                      timeout: Option<Duration>,
+                      deadline: Option<Instant>)
+                      -> Result<Connected, ConnectError> {
+        let r = self.connect_resolved(host, port, timeout, deadline);
+        if self.sticky_addresses {
+            match r {
+                Ok(ref connected) => self.sticky_addrs.insert(host, port, connected.info.addr),
+                Err(_) => self.sticky_addrs.remove(host, port),
+            }
+        }
+        r
+    }
+
+    fn connect_resolved(&self,
+                        host: &str,
+                        port: u16,
+                        timeout: Option<Duration>,
+                        deadline: Option<Instant>)
+                        -> Result<Connected, ConnectError> {
+        let start = Instant::now();
+        let (addrs, cached) =
+            self.resolve_until(host, port, deadline).map_err(ConnectError::Resolve)?;
+        let resolve_time = start.elapsed();
+        let start = Instant::now();
+        if let Some(ref hook) = self.resolve_hook {
+            hook(host, &addrs);
+        }
+        if addrs.is_empty() {
+            warn!("failed to connect to {}:{}: no addresses", host, port);
+            return Err(ConnectError::no_addresses(host));
+        }
+        let addrs = match self.filter_addrs(host, addrs) {
+            Ok(addrs) => addrs,
+            Err(e) => {
+                warn!("failed to connect to {}:{}: {}", host, port, e);
+                return Err(e);
+            }
+        };
+
+        let mut retries = 0;
+        loop {
+            let err = match self.connect_addrs_until(addrs.clone(), timeout, deadline) {
+                Ok(mut connected) => {
+                    connected.info.cached = cached;
+                    connected.info.timing.resolve = resolve_time;
+                    connected.info.timing.connect = start.elapsed();
+                    return Ok(connected);
+                }
+                Err(e) => e,
+            };
+
+            let delay = self.retry_delay();
+            let out_of_time = match deadline {
+                Some(deadline) => Instant::now() + delay >= deadline,
+                None => false,
+            };
+            if retries >= self.retries || !is_transient(&err) || out_of_time {
+                warn!("failed to connect to {}:{}: {}", host, port, err);
+                return Err(ConnectError::from_connect(err));
+            }
+
+            debug!("retrying connection to {}:{} after error: {}", host, port, err);
+            retries += 1;
+            thread::sleep(delay);
+        }
+    }
+
+    fn retry_delay(&self) -> Duration {
+        if self.retry_jitter > 0.0 {
+            let factor = 1.0 + self.retry_jitter * (2.0 * rng::next_f64() - 1.0);
+            self.retry_backoff.mul_f64(factor)
+        } else {
+            self.retry_backoff
+        }
+    }
+
+    fn connect_addrs_until(&self,
+                           addrs: Vec<SocketAddr>,
+                           timeout: Option<Duration>,
+                           deadline: Option<Instant>)
+                           -> io::Result<Connected> {
+        if self.happy_eyeballs {
+            happy_eyeballs::connect(self, addrs, timeout, deadline)
+        } else if self.parallel_attempts > 1 {
+            let delay = Duration::from_secs(0);
+            happy_eyeballs::race(self, addrs, delay, self.parallel_attempts, timeout, deadline)
+        } else {
+            self.connect_sequential(addrs, timeout, deadline)
+        }
+    }
+
+    /// Connects to a single address.
+    ///
+    /// This skips hostname resolution entirely, but otherwise applies all of
+    /// the connector's configuration, with the connection timeout and total
+    /// timeout both bounding the attempt.
+    pub fn connect_addr(&self, addr: SocketAddr) -> io::Result<HttpStream> {
+        let start = Instant::now();
+        let deadline = self.deadline();
+        let r = self.acquire_permit(deadline).and_then(|_permit| {
+            let timeout = remaining(self.connect_timeout, deadline).map_err(ConnectError::Timeout)?;
+            self.attempt(addr, timeout).map_err(ConnectError::from_connect)
+        });
+        let r = r.map_err(|e| e.check_deadline(deadline));
+        self.complete(r, start)
+            .map(|c| HttpStream(c.stream))
+            .map_err(io::Error::from)
+    }
+
+    /// Connects to the first reachable address of a list, trying them in
+    /// order.
+    ///
+    /// Like `connect_addr`, this skips hostname resolution entirely, but
+    /// otherwise applies all of the connector's configuration. The addresses
+    /// are not reordered or filtered, and are raced against each other if
+    /// Happy Eyeballs is enabled.
+    pub fn connect_addrs(&self, addrs: &[SocketAddr]) -> io::Result<HttpStream> {
+        let start = Instant::now();
+        let r = if addrs.is_empty() {
+            let err = io::Error::new(io::ErrorKind::InvalidInput, "no addresses to connect to");
+            Err(ConnectError::NoAddresses(err))
+        } else {
+            let deadline = self.deadline();
+            self.acquire_permit(deadline)
+                .and_then(|_permit| {
+                    self.connect_addrs_until(addrs.to_vec(), self.connect_timeout, deadline)
+                        .map_err(ConnectError::from_connect)
+                })
+                .map_err(|e| e.check_deadline(deadline))
+        };
+        self.complete(r, start)
+            .map(|c| HttpStream(c.stream))
+            .map_err(io::Error::from)
+    }
+
+    fn attempt(&self, addr: SocketAddr, timeout: Option<Duration>) -> io::Result<Connected> {
+        let start = Instant::now();
+        match self.connect_once(addr, timeout) {
+            Ok(stream) => {
+                let elapsed = start.elapsed();
+                self.attempt_succeeded(addr, elapsed);
+                Ok(Connected {
+                    stream,
+                    info: ConnectInfo::new(addr, elapsed),
+                })
+            }
+            Err(e) => {
+                self.attempt_failed(addr, &e, start.elapsed());
+                Err(e)
+            }
+        }
+    }
+
+    fn attempt_succeeded(&self, addr: SocketAddr, elapsed: Duration) {
+        debug!("connected to {} in {:?}", addr, elapsed);
+        if self.address_cooldown.is_some() {
+            self.failed_addrs.remove(&addr);
+        }
+        if let Some(ref hook) = self.connect_success_hook {
+            hook(addr, elapsed);
+        }
+    }
+
+    fn attempt_failed(&self, addr: SocketAddr, err: &io::Error, elapsed: Duration) {
+        debug!("connection to {} failed after {:?}: {}", addr, elapsed, err);
+        if let Some(cooldown) = self.address_cooldown {
+            self.failed_addrs.insert(addr, Instant::now() + cooldown);
+        }
+        if let Some(ref hook) = self.connect_failure_hook {
+            hook(addr, err);
+        }
+    }
+
+    fn connect_sequential(&self,
+                          addrs: Vec<SocketAddr>,
+                          timeout: Option<Duration>,
+                          deadline: Option<Instant>)
+                          -> io::Result<Connected> {
+        let addresses = addrs.len();
+        let mut fallback_timeout = self.v6_fallback_timeout
+            .filter(|_| addrs.iter().any(|a| a.is_ipv4()) && addrs.iter().any(|a| a.is_ipv6()));
+        let mut errors = vec![];
+        let mut attempts = vec![];
+        for (index, addr) in addrs.into_iter().enumerate() {
+            let connect_timeout = match fallback_timeout {
+                Some(fallback) if addr.is_ipv6() => {
+                    fallback_timeout = None;
+                    match timeout {
+                        Some(timeout) if timeout < fallback => Some(timeout),
+                        _ => Some(fallback),
+                    }
+                }
+                _ => timeout,
+            };
+            let mut retries = 0;
+            let err = loop {
+                let attempt_timeout = match remaining(connect_timeout, deadline) {
+                    Ok(timeout) => timeout,
+                    Err(e) => {
+                        errors.push((addr, e));
+                        return Err(error::attempts_failed(errors));
+                    }
+                };
+                let start = Instant::now();
+                let r = self.attempt(addr, attempt_timeout);
+                attempts.push((addr, start.elapsed()));
+                match r {
+                    Ok(mut connected) => {
+                        connected.info.index = index;
+                        connected.info.addresses = addresses;
+                        connected.info.failed = errors.len();
+                        connected.info.timing.attempts = attempts;
+                        return Ok(connected);
+                    }
+                    Err(ref e) if retries < self.per_address_retries && is_transient(e) => {
+                        retries += 1;
+                    }
+                    Err(e) => break e,
+                }
+            };
+            let exhausted = error::is_fd_exhausted(&err);
+            errors.push((addr, err));
+            if exhausted {
+                // the remaining addresses would fail in the same way
+                break;
+            }
+        }
+
+        Err(error::attempts_failed(errors))
+    }
+}
+
+impl fmt::Debug for HttpTimeoutConnector {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("HttpTimeoutConnector")
+            .field("connect_timeout", &self.connect_timeout)
+            .field("timeout_strategy", &self.timeout_strategy)
+            .field("scheme_connect_timeouts", &self.scheme_connect_timeouts)
+            .field("resolve_timeout", &self.resolve_timeout)
+            .field("total_timeout", &self.total_timeout)
+            .field("read_timeout", &self.read_timeout)
+            .field("write_timeout", &self.write_timeout)
+            .field("handshake_timeout", &self.handshake_timeout)
+            .field("nonblocking_stream", &self.nonblocking_stream)
+            .field("tos", &self.tos)
+            .field("priority", &self.priority)
+            .field("user_timeout", &self.user_timeout)
+            .field("tcp_fastopen", &self.tcp_fastopen)
+            .field("mss", &self.mss)
+            .field("congestion_control", &self.congestion_control)
+            .field("ignore_option_errors", &self.ignore_option_errors)
+            .field("send_buffer_size", &self.send_buffer_size)
+            .field("recv_buffer_size", &self.recv_buffer_size)
+            .field("linger", &self.linger)
+            .field("max_concurrent_connects", &self.max_concurrent_connects)
+            .field("connect_queue_timeout", &self.connect_queue_timeout)
+            .field("retries", &self.retries)
+            .field("per_address_retries", &self.per_address_retries)
+            .field("retry_backoff", &self.retry_backoff)
+            .field("retry_jitter", &self.retry_jitter)
+            .field("socket_configurator", &self.socket_configurator.as_ref().map(|_| ".."))
+            .field("connect_success_hook", &self.connect_success_hook.as_ref().map(|_| ".."))
+            .field("connect_failure_hook", &self.connect_failure_hook.as_ref().map(|_| ".."))
+            .field("resolve_hook", &self.resolve_hook.as_ref().map(|_| ".."))
+            .field("proxy", &self.proxy)
+            .field("proxy_bypass", &self.proxy_bypass)
+            .field("hosts", &self.hosts)
+            .field("fallback_host", &self.fallback_host)
+            .field("proxy_protocol", &self.proxy_protocol)
+            .field("happy_eyeballs", &self.happy_eyeballs)
+            .field("happy_eyeballs_delay", &self.happy_eyeballs_delay)
+            .field("max_parallel_attempts", &self.max_parallel_attempts)
+            .field("parallel_attempts", &self.parallel_attempts)
+            .field("v6_fallback_timeout", &self.v6_fallback_timeout)
+            .field("nodelay", &self.nodelay)
+            .field("keepalive", &self.keepalive)
+            .field("local_address", &self.local_address)
+            .field("local_port_range", &self.local_port_range)
+            .field("bind_device", &self.bind_device)
+            .field("reuse_address", &self.reuse_address)
+            .field("reuse_port", &self.reuse_port)
+            .field("freebind", &self.freebind)
+            .field("transparent", &self.transparent)
+            .field("ttl", &self.ttl)
+            .field("address_family", &self.address_family)
+            .field("disable_ipv6", &self.disable_ipv6)
+            .field("resolver", &self.resolver.as_ref().map(|_| ".."))
+            .field("host_overrides", &self.host_overrides)
+            .field("address_sort", &self.address_sort.as_ref().map(|_| ".."))
+            .field("address_filter", &self.address_filter.as_ref().map(|_| ".."))
+            .field("block_private_addresses", &self.block_private_addresses)
+            .field("dns_cache_ttl", &self.dns_cache_ttl)
+            .field("max_attempts", &self.max_attempts)
+            .field("shuffle_addresses", &self.shuffle_addresses)
+            .field("sticky_addresses", &self.sticky_addresses)
+            .field("address_cooldown", &self.address_cooldown)
+            .field("allowed_schemes", &self.allowed_schemes)
+            .field("strict_scheme", &self.strict_scheme)
+            .finish()
+    }
+}
+
+impl Default for HttpTimeoutConnector {
+    fn default() -> HttpTimeoutConnector {
+        HttpTimeoutConnector::new()
+    }
+}
+
+/// A builder for `HttpTimeoutConnector`s.
+///
+/// # Examples
+///
+/// ```
+/// use hyper_timeout_connector::HttpTimeoutConnector;
+/// use std::time::Duration;
+///
+/// let connector = HttpTimeoutConnector::builder()
+///     .connect_timeout(Duration::from_secs(30))
+///     .nodelay(true)
+///     .build();
+/// ```
+#[derive(Clone, Debug)]
+pub struct HttpTimeoutConnectorBuilder(HttpTimeoutConnector);
+
+impl HttpTimeoutConnectorBuilder {
+    /// Sets the connection timeout.
+    pub fn connect_timeout(mut self, timeout: Duration) -> HttpTimeoutConnectorBuilder {
+        self.0.set_connect_timeout(Some(timeout));
+        self
+    }
+
+    /// Sets how connection timeouts are enforced.
+    pub fn timeout_strategy(mut self, strategy: TimeoutStrategy) -> HttpTimeoutConnectorBuilder {
+        self.0.set_timeout_strategy(strategy);
+        self
+    }
+
+    /// Sets the connection timeout used for connect calls with the given URL
+    /// scheme.
+    pub fn connect_timeout_for_scheme(mut self,
+                                      scheme: &str,
+                                      timeout: Duration)
+                                      -> HttpTimeoutConnectorBuilder {
+        self.0.set_connect_timeout_for_scheme(scheme, Some(timeout));
+        self
+    }
+
+    /// Sets the hostname resolution timeout.
+    pub fn resolve_timeout(mut self, timeout: Duration) -> HttpTimeoutConnectorBuilder {
+        self.0.set_resolve_timeout(Some(timeout));
+        self
+    }
+
+    /// Sets the total timeout.
+    pub fn total_timeout(mut self, timeout: Duration) -> HttpTimeoutConnectorBuilder {
+        self.0.set_total_timeout(Some(timeout));
+        self
+    }
+
+    /// Sets the read timeout set on new connections.
+    pub fn read_timeout(mut self, timeout: Duration) -> HttpTimeoutConnectorBuilder {
+        self.0.set_read_timeout(Some(timeout));
+        self
+    }
+
+    /// Sets the write timeout set on new connections.
+    pub fn write_timeout(mut self, timeout: Duration) -> HttpTimeoutConnectorBuilder {
+        self.0.set_write_timeout(Some(timeout));
+        self
+    }
+
+    /// Sets the timeout set on new connections for the handshake of a
+    /// wrapping protocol such as TLS.
+    pub fn handshake_timeout(mut self, timeout: Duration) -> HttpTimeoutConnectorBuilder {
+        self.0.set_handshake_timeout(Some(timeout));
+        self
+    }
+
+    /// Sets whether new connections are returned in nonblocking mode.
+    pub fn nonblocking_stream(mut self, nonblocking: bool) -> HttpTimeoutConnectorBuilder {
+        self.0.set_nonblocking_stream(nonblocking);
+        self
+    }
+
+    /// Enables or disables Happy Eyeballs connection racing.
+    pub fn happy_eyeballs(mut self, happy_eyeballs: bool) -> HttpTimeoutConnectorBuilder {
+        self.0.set_happy_eyeballs(happy_eyeballs);
+        self
+    }
+
+    /// Sets the delay between the start of consecutive Happy Eyeballs
+    /// connection attempts.
+    pub fn happy_eyeballs_delay(mut self, delay: Duration) -> HttpTimeoutConnectorBuilder {
+        self.0.set_happy_eyeballs_delay(delay);
+        self
+    }
+
+    /// Sets the maximum number of connection attempts Happy Eyeballs keeps in
+    /// flight at once.
+    pub fn max_parallel_attempts(mut self,
+                                 max_parallel_attempts: usize)
+                                 -> HttpTimeoutConnectorBuilder {
+        self.0.set_max_parallel_attempts(max_parallel_attempts);
+        self
+    }
+
+    /// Sets the number of connection attempts started at once when Happy
+    /// Eyeballs is disabled.
+    pub fn parallel_attempts(mut self, parallel_attempts: usize) -> HttpTimeoutConnectorBuilder {
+        self.0.set_parallel_attempts(parallel_attempts);
+        self
+    }
+
+    /// Sets the timeout of the first IPv6 connection attempt to hosts with
+    /// both IPv4 and IPv6 addresses.
+    pub fn v6_fallback_timeout(mut self, timeout: Duration) -> HttpTimeoutConnectorBuilder {
+        self.0.set_v6_fallback_timeout(Some(timeout));
+        self
+    }
+
+    /// Sets whether `TCP_NODELAY` is set on new connections.
+    pub fn nodelay(mut self, nodelay: bool) -> HttpTimeoutConnectorBuilder {
+        self.0.set_nodelay(nodelay);
+        self
+    }
+
+    /// Sets the TCP keepalive idle time for new connections.
+    pub fn keepalive(mut self, keepalive: Duration) -> HttpTimeoutConnectorBuilder {
+        self.0.set_keepalive(Some(keepalive));
+        self
+    }
+
+    /// Sets the local address new connections are bound to.
+    pub fn local_address(mut self, addr: SocketAddr) -> HttpTimeoutConnectorBuilder {
+        self.0.set_local_address(Some(addr));
+        self
+    }
+
+    /// Sets the inclusive range of local ports new connections are bound to.
+    pub fn local_port_range(mut self, start: u16, end: u16) -> HttpTimeoutConnectorBuilder {
+        self.0.set_local_port_range(Some((start, end)));
+        self
+    }
+
+    /// Sets the network interface new connections are bound to.
+    pub fn bind_device(mut self, device: String) -> HttpTimeoutConnectorBuilder {
+        self.0.set_bind_device(Some(device));
+        self
+    }
+
+    /// Sets the IP time-to-live of packets sent over new connections.
+    pub fn ttl(mut self, ttl: u32) -> HttpTimeoutConnectorBuilder {
+        self.0.set_ttl(Some(ttl));
+        self
+    }
+
+    /// Sets the IP type-of-service value of packets sent over new connections.
+    pub fn tos(mut self, tos: u32) -> HttpTimeoutConnectorBuilder {
+        self.0.set_tos(Some(tos));
+        self
+    }
+
+    /// Sets the priority of packets sent over new connections.
+    pub fn priority(mut self, priority: u32) -> HttpTimeoutConnectorBuilder {
+        self.0.set_priority(Some(priority));
+        self
+    }
+
+    /// Enables or disables TCP Fast Open for new connections.
+    pub fn tcp_fastopen(mut self, tcp_fastopen: bool) -> HttpTimeoutConnectorBuilder {
+        self.0.set_tcp_fastopen(tcp_fastopen);
+        self
+    }
+
+    /// Sets the TCP user timeout of new connections.
+    pub fn user_timeout(mut self, timeout: Duration) -> HttpTimeoutConnectorBuilder {
+        self.0.set_user_timeout(Some(timeout));
+        self
+    }
+
+    /// Sets the TCP maximum segment size requested for new connections.
+    pub fn mss(mut self, mss: u32) -> HttpTimeoutConnectorBuilder {
+        self.0.set_mss(Some(mss));
+        self
+    }
+
+    /// Sets the TCP congestion control algorithm used by new connections.
+    pub fn congestion_control(mut self, algorithm: String) -> HttpTimeoutConnectorBuilder {
+        self.0.set_congestion_control(Some(algorithm));
+        self
+    }
+
+    /// Sets the send buffer size requested for new connections.
+    pub fn send_buffer_size(mut self, size: usize) -> HttpTimeoutConnectorBuilder {
+        self.0.set_send_buffer_size(Some(size));
+        self
+    }
+
+    /// Sets the receive buffer size requested for new connections.
+    pub fn recv_buffer_size(mut self, size: usize) -> HttpTimeoutConnectorBuilder {
+        self.0.set_recv_buffer_size(Some(size));
+        self
+    }
+
+    /// Sets the `SO_LINGER` configuration of new connections.
+    ///
+    /// See `HttpTimeoutConnector::set_linger` for details.
+    pub fn linger(mut self, linger: Option<Duration>) -> HttpTimeoutConnectorBuilder {
+        self.0.set_linger(Some(linger));
+        self
+    }
+
+    /// Sets whether `SO_REUSEADDR` is set on new connections.
+    pub fn reuse_address(mut self, reuse_address: bool) -> HttpTimeoutConnectorBuilder {
+        self.0.set_reuse_address(reuse_address);
+        self
+    }
+
+    /// Sets whether `SO_REUSEPORT` is set on new connections.
+    pub fn reuse_port(mut self, reuse_port: bool) -> HttpTimeoutConnectorBuilder {
+        self.0.set_reuse_port(reuse_port);
+        self
+    }
+
+    /// Sets whether new connections may be bound to non-local addresses.
+    pub fn freebind(mut self, freebind: bool) -> HttpTimeoutConnectorBuilder {
+        self.0.set_freebind(freebind);
+        self
+    }
+
+    /// Sets whether new connections are made in transparent proxy mode.
+    pub fn transparent(mut self, transparent: bool) -> HttpTimeoutConnectorBuilder {
+        self.0.set_transparent(transparent);
+        self
+    }
+
+    /// Sets which address families are used to connect to a host.
+    pub fn address_family(mut self, family: AddressFamily) -> HttpTimeoutConnectorBuilder {
+        self.0.set_address_family(family);
+        self
+    }
+
+    /// Sets whether IPv6 addresses are discarded after resolution.
+    pub fn disable_ipv6(mut self, disable_ipv6: bool) -> HttpTimeoutConnectorBuilder {
+        self.0.set_disable_ipv6(disable_ipv6);
+        self
+    }
+
+    /// Sets a custom hostname resolver.
+    pub fn resolver(mut self, resolver: Box<Resolver>) -> HttpTimeoutConnectorBuilder {
+        self.0.set_resolver(Some(resolver));
+        self
+    }
+
+    /// Overrides the addresses a host resolves to.
+    pub fn host_override(mut self,
+                         host: String,
+                         addrs: Vec<IpAddr>)
+                         -> HttpTimeoutConnectorBuilder {
+        self.0.add_host_override(host, addrs);
+        self
+    }
+
+    /// Sets a comparator used to order resolved addresses.
+    pub fn address_sort(mut self, sort: Box<AddressSort>) -> HttpTimeoutConnectorBuilder {
+        self.0.set_address_sort(Some(sort));
+        self
+    }
+
+    /// Sets a callback used to reject resolved addresses.
+    pub fn address_filter(mut self, filter: Box<AddressFilter>) -> HttpTimeoutConnectorBuilder {
+        self.0.set_address_filter(Some(filter));
+        self
+    }
+
+    /// Sets whether connections to private addresses are blocked.
+    pub fn block_private_addresses(mut self,
+                                   block_private_addresses: bool)
+                                   -> HttpTimeoutConnectorBuilder {
+        self.0.set_block_private_addresses(block_private_addresses);
+        self
+    }
+
+    /// Sets the time resolved addresses are cached for.
+    pub fn dns_cache_ttl(mut self, ttl: Duration) -> HttpTimeoutConnectorBuilder {
+        self.0.set_dns_cache_ttl(Some(ttl));
+        self
+    }
+
+    /// Sets the maximum number of connect calls which may be in progress at
+    /// once.
+    pub fn max_concurrent_connects(mut self, max: usize) -> HttpTimeoutConnectorBuilder {
+        self.0.set_max_concurrent_connects(Some(max));
+        self
+    }
+
+    /// Sets the maximum time a connect call waits for other connect calls to
+    /// finish when the concurrent connect limit is reached.
+    pub fn connect_queue_timeout(mut self, timeout: Duration) -> HttpTimeoutConnectorBuilder {
+        self.0.set_connect_queue_timeout(Some(timeout));
+        self
+    }
+
+    /// Sets the number of times a failed connect call is retried.
+    pub fn retries(mut self, retries: usize) -> HttpTimeoutConnectorBuilder {
+        self.0.set_retries(retries);
+        self
+    }
+
+    /// Sets the number of times a failed attempt to connect to an address is
+    /// retried before moving on to the next address.
+    pub fn per_address_retries(mut self, retries: usize) -> HttpTimeoutConnectorBuilder {
+        self.0.set_per_address_retries(retries);
+        self
+    }
+
+    /// Sets the time waited between retries.
+    pub fn retry_backoff(mut self, backoff: Duration) -> HttpTimeoutConnectorBuilder {
+        self.0.set_retry_backoff(backoff);
+        self
+    }
+
+    /// Sets the fraction of the retry backoff by which it is randomly varied.
+    pub fn retry_jitter(mut self, jitter: f64) -> HttpTimeoutConnectorBuilder {
+        self.0.set_retry_jitter(jitter);
+        self
+    }
+
+    /// Sets whether errors setting optional socket options are ignored.
+    pub fn ignore_option_errors(mut self,
+                                ignore_option_errors: bool)
+                                -> HttpTimeoutConnectorBuilder {
+        self.0.set_ignore_option_errors(ignore_option_errors);
+        self
+    }
+
+    /// Sets a callback used to configure new sockets.
+    pub fn socket_configurator(mut self,
+                               configurator: Box<SocketConfigurator>)
+                               -> HttpTimeoutConnectorBuilder {
+        self.0.set_socket_configurator(Some(configurator));
+        self
+    }
+
+    /// Sets a callback invoked after each successful connection attempt.
+    pub fn connect_success_hook(mut self, hook: Box<SuccessHook>) -> HttpTimeoutConnectorBuilder {
+        self.0.set_connect_success_hook(Some(hook));
+        self
+    }
+
+    /// Sets a callback invoked after each failed connection attempt.
+    pub fn connect_failure_hook(mut self, hook: Box<FailureHook>) -> HttpTimeoutConnectorBuilder {
+        self.0.set_connect_failure_hook(Some(hook));
+        self
+    }
+
+    /// Sets a callback invoked after a host is resolved during a connect call.
+    pub fn resolve_hook(mut self, hook: Box<ResolveHook>) -> HttpTimeoutConnectorBuilder {
+        self.0.set_resolve_hook(Some(hook));
+        self
+    }
+
+    /// Sets the proxy connections are tunneled through.
+    pub fn proxy(mut self, proxy: ProxyConfig) -> HttpTimeoutConnectorBuilder {
+        self.0.set_proxy(Some(proxy));
+        self
+    }
+
+    /// Sets the hosts which are connected to directly rather than through the
+    /// proxy.
+    pub fn proxy_bypass(mut self, bypass: Vec<String>) -> HttpTimeoutConnectorBuilder {
+        self.0.set_proxy_bypass(bypass);
+        self
+    }
+
+    /// Sets the version of the PROXY protocol header sent on new connections.
+    pub fn proxy_protocol(mut self, version: ProxyProtocolVersion) -> HttpTimeoutConnectorBuilder {
+        self.0.set_proxy_protocol(Some(version));
+        self
+    }
+
+    /// Sets the hosts connected to in place of the requested host.
+    pub fn hosts(mut self, hosts: Vec<(String, u16)>) -> HttpTimeoutConnectorBuilder {
+        self.0.set_hosts(hosts);
+        self
+    }
+
+    /// Sets the host and port connected to if connecting to the requested
+    /// host fails.
+    pub fn fallback_host(mut self, host: String, port: u16) -> HttpTimeoutConnectorBuilder {
+        self.0.set_fallback_host(Some((host, port)));
+        self
+    }
+
+    /// Sets the maximum number of addresses tried per connect call.
+    pub fn max_attempts(mut self, max_attempts: usize) -> HttpTimeoutConnectorBuilder {
+        self.0.set_max_attempts(Some(max_attempts));
+        self
+    }
+
+    /// Sets whether resolved addresses are shuffled.
+    pub fn shuffle_addresses(mut self, shuffle_addresses: bool) -> HttpTimeoutConnectorBuilder {
+        self.0.set_shuffle_addresses(shuffle_addresses);
+        self
+    }
+
+    /// Sets the time for which an address is deprioritized after a
+    /// connection attempt to it fails.
+    pub fn address_cooldown(mut self, cooldown: Duration) -> HttpTimeoutConnectorBuilder {
+        self.0.set_address_cooldown(Some(cooldown));
+        self
+    }
+
+    /// Sets whether the address last connected to for a host is tried first.
+    pub fn sticky_addresses(mut self, sticky_addresses: bool) -> HttpTimeoutConnectorBuilder {
+        self.0.set_sticky_addresses(sticky_addresses);
+        self
+    }
+
+    /// Sets the URL schemes accepted by connect calls.
+    pub fn allowed_schemes(mut self, schemes: Vec<String>) -> HttpTimeoutConnectorBuilder {
+        self.0.set_allowed_schemes(schemes);
+        self
+    }
+
+    /// Sets whether the scheme of connect calls is checked.
+    pub fn strict_scheme(mut self, strict_scheme: bool) -> HttpTimeoutConnectorBuilder {
+        self.0.set_strict_scheme(strict_scheme);
+        self
+    }
+
+    /// Consumes the builder, returning the configured connector.
+    pub fn build(self) -> HttpTimeoutConnector {
+        self.0
+    }
+}
+
+impl NetworkConnector for HttpTimeoutConnector {
+    type Stream = HttpStream;
+
+    fn connect(&self, host: &str, port: u16, scheme: &str) -> hyper::Result<HttpStream> {
+        self.connect_timed(host, port, scheme)
+            .map(|(stream, _)| stream)
+            .map_err(From::from)
+    }
+}
+
+fn resolve_with(resolver: Option<&Resolver>, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+    match resolver {
+        Some(resolver) => resolver(host, port),
+        None => Ok((host, port).to_socket_addrs()?.collect()),
+    }
+}
+
+/// Rejects hosts which are obviously malformed, such as a URL passed instead
+/// of a hostname.
+fn validate_host(host: &str) -> io::Result<()> {
+    let msg = if host.is_empty() {
+        "host is empty".to_owned()
+    } else if host.contains("://") {
+        format!("host `{}` must not include a URL scheme", host)
+    } else if host.chars().any(char::is_whitespace) {
+        format!("host `{}` contains whitespace", host)
+    } else {
+        return Ok(());
+    };
+
+    Err(io::Error::new(io::ErrorKind::InvalidInput, msg))
+}
+
+/// Determines the address to bind to when connecting to `remote`.
+///
+/// Link-local IPv6 addresses are only meaningful in combination with a scope
+/// ID, so a link-local local address inherits the scope ID of the remote
+/// address.
+fn local_bind_addr(local: SocketAddr, remote: &SocketAddr) -> SocketAddr {
+    match (local, *remote) {
+        (SocketAddr::V6(ref local), SocketAddr::V6(ref remote))
+            if local.ip().segments()[0] & 0xffc0 == 0xfe80 && local.scope_id() == 0 => {
+            SocketAddr::V6(SocketAddrV6::new(*local.ip(),
+                                             local.port(),
+                                             local.flowinfo(),
+                                             remote.scope_id()))
+        }
+        (local, _) => local,
+    }
+}
+
+/// Binds the socket to a free port in the inclusive range.
+fn bind_in_range(socket: &Socket,
+                 mut local: SocketAddr,
+                 (start, end): (u16, u16))
+                 -> io::Result<()> {
+    if start > end {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid local port range"));
+    }
+
+    let len = u64::from(end - start) + 1;
+    let offset = rng::next_u64() % len;
+    for i in 0..len {
+        local.set_port(start + ((offset + i) % len) as u16);
+        match socket.bind(&SockAddr::from(local)) {
+            Ok(()) => return Ok(()),
+            Err(ref e) if e.kind() == io::ErrorKind::AddrInUse => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    Err(io::Error::new(io::ErrorKind::AddrInUse, "no free local port in range"))
+}
+
+fn connect_socket(socket: &Socket,
+                  addr: SocketAddr,
+                  timeout: Option<Duration>,
+                  strategy: TimeoutStrategy)
+                  -> io::Result<()> {
+    let sockaddr = SockAddr::from(addr);
+    let r = match (timeout, strategy) {
+        // the behavior of a zero timeout is platform dependent, and it may
+        // even succeed if the connection completes immediately
+        (Some(timeout), _) if timeout == Duration::from_secs(0) => {
+            Err(io::Error::new(io::ErrorKind::TimedOut, "connection timed out"))
+        }
+        (Some(timeout), TimeoutStrategy::Native) => socket.connect_timeout(&sockaddr, timeout),
+        (Some(timeout), TimeoutStrategy::WatchdogThread) => {
+            connect_watchdog(socket, &sockaddr, timeout)
+        }
+        (None, _) => socket.connect(&sockaddr),
+    };
+
+    match (r, timeout) {
+        (Err(ref e), Some(timeout)) if e.kind() == io::ErrorKind::TimedOut => {
+            Err(io::Error::new(io::ErrorKind::TimedOut,
+                               format!("connection to {} timed out after {:?}", addr, timeout)))
+        }
+        (r, _) => r,
+    }
+}
+
+/// Connects in blocking mode, shutting the socket down from another thread if
+/// the timeout elapses first.
+fn connect_watchdog(socket: &Socket, addr: &SockAddr, timeout: Duration) -> io::Result<()> {
+    let watched = socket.try_clone()?;
+    let timed_out = Arc::new(AtomicBool::new(false));
+    let (tx, rx) = mpsc::channel::<()>();
+
+    let timed_out2 = timed_out.clone();
+    let watchdog = thread::spawn(move || {
+        if let Err(RecvTimeoutError::Timeout) = rx.recv_timeout(timeout) {
+            timed_out2.store(true, AtomicOrdering::SeqCst);
+            let _ = watched.shutdown(Shutdown::Both);
+        }
+    });
+
+    let r = socket.connect(addr);
+    drop(tx);
+    let _ = watchdog.join();
+
+    if timed_out.load(AtomicOrdering::SeqCst) {
+        return Err(io::Error::new(io::ErrorKind::TimedOut, "connection timed out"));
+    }
+    r
+}
+
+/// Determines if a connection error may go away if the attempt is retried.
+fn is_transient(err: &io::Error) -> bool {
+    matches!(err.kind(),
+             io::ErrorKind::ConnectionRefused |
+             io::ErrorKind::ConnectionReset |
+             io::ErrorKind::ConnectionAborted |
+             io::ErrorKind::TimedOut)
+}
+
+/// Determines if an address is loopback, link-local, or otherwise private.
+fn is_private(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            ip.is_private() || ip.is_loopback() || ip.is_link_local() || ip.octets()[0] == 0
+        }
+        IpAddr::V6(ip) => {
+            let segments = ip.segments();
+            if segments[..5] == [0; 5] && segments[5] == 0xffff {
+                let [a, b] = segments[6].to_be_bytes();
+                let [c, d] = segments[7].to_be_bytes();
+                return is_private(IpAddr::V4(Ipv4Addr::new(a, b, c, d)));
+            }
+
+            ip.is_loopback() || ip.is_unspecified() || segments[0] & 0xffc0 == 0xfe80 ||
+            segments[0] & 0xfe00 == 0xfc00
+        }
+    }
+}
+
+/// Returns the shorter of `timeout` and the time left until `deadline`.
+///
+/// Fails with a `TimedOut` error if the deadline has already passed.
+fn remaining(timeout: Option<Duration>, deadline: Option<Instant>) -> io::Result<Option<Duration>> {
+    let deadline = match deadline {
+        Some(deadline) => deadline,
+        None => return Ok(timeout),
+    };
+
+    let now = Instant::now();
+    if now >= deadline {
+        return Err(io::Error::new(io::ErrorKind::TimedOut, "connection timed out"));
+    }
+
+    let left = deadline - now;
+    match timeout {
+        Some(timeout) if timeout < left => Ok(Some(timeout)),
+        _ => Ok(Some(left)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use hyper::{self, Client};
+    use std::io::Read;
+    use std::net::TcpListener;
+    use std::sync::Mutex;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[test]
+    fn timeout() {
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_connect_timeout(Some(Duration::from_millis(250)));
+
+        let client = Client::with_connector(connector);
+
+        // this is an unroutable IP so connection should always time out
+        match client.get("http://10.255.255.1:80").send() {
+            Ok(_) => panic!("unexpected success"),
+            Err(hyper::Error::Io(ref e)) if e.kind() == io::ErrorKind::TimedOut => {}
+            Err(e) => panic!("unexpected error {:?}", e),
+        }
+    }
+
+    #[test]
+    fn resolve_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_resolve_timeout(Some(Duration::from_secs(5)));
+        connector.connect("localhost", port, "http").unwrap();
+    }
+
+    #[test]
+    fn total_timeout() {
+        let (addr, _sockets) = unresponsive();
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_connect_timeout(Some(Duration::from_secs(30)));
+        connector.set_total_timeout(Some(Duration::from_millis(250)));
+
+        let start = Instant::now();
+        match connector.connect(&addr.ip().to_string(), addr.port(), "http") {
+            Ok(_) => panic!("unexpected success"),
+            Err(hyper::Error::Io(ref e)) if e.kind() == io::ErrorKind::TimedOut => {}
+            Err(e) => panic!("unexpected error {:?}", e),
+        }
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn happy_eyeballs() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_connect_timeout(Some(Duration::from_secs(5)));
+        connector.set_happy_eyeballs(true);
+        connector.connect("localhost", port, "http").unwrap();
+
+        connector.set_happy_eyeballs_delay(Duration::from_millis(20));
+        connector.connect("localhost", port, "http").unwrap();
+    }
+
+    #[test]
+    fn max_parallel_attempts() {
+        let refused1 = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let refused2 = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_happy_eyeballs(true);
+        connector.set_max_parallel_attempts(1);
+        connector.set_resolver(Some(Box::new(move |_, _| Ok(vec![refused1, refused2, addr]))));
+        let (_, info) = connector.connect_with_info("example.invalid", 80, "http").unwrap();
+        assert_eq!(info.addr(), addr);
+        assert_eq!(info.failed_attempts(), 2);
+    }
+
+    #[test]
+    fn nodelay() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_nodelay(true);
+        let stream = connector.connect_once(addr, None).unwrap();
+        assert!(stream.nodelay().unwrap());
+    }
+
+    #[test]
+    fn keepalive() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_keepalive(Some(Duration::from_secs(60)));
+        let stream = connector.connect_once(addr, None).unwrap();
+        let socket = Socket::from(stream);
+        assert_eq!(socket.keepalive().unwrap(), Some(Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn reuse_address() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut connector = HttpTimeoutConnector::new();
+        let socket = Socket::from(connector.connect_once(addr, None).unwrap());
+        assert!(!socket.reuse_address().unwrap());
+
+        connector.set_reuse_address(true);
+        let socket = Socket::from(connector.connect_once(addr, None).unwrap());
+        assert!(socket.reuse_address().unwrap());
+    }
+
+    #[test]
+    fn local_address() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_local_address(Some("127.0.0.1:0".parse().unwrap()));
+        let stream = connector.connect_once(addr, None).unwrap();
+        assert_eq!(stream.local_addr().unwrap().ip(), addr.ip());
+
+        connector.set_local_address(Some("[::1]:0".parse().unwrap()));
+        let err = connector.connect_once(addr, None).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn local_port_range() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_local_port_range(Some((port, port)));
+        let stream = connector.connect_once(addr, None).unwrap();
+        assert_eq!(stream.local_addr().unwrap().port(), port);
+
+        connector.set_local_port_range(Some((addr.port(), addr.port())));
+        let err = connector.connect_once(addr, None).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AddrInUse);
+
+        connector.set_local_port_range(Some((2, 1)));
+        let err = connector.connect_once(addr, None).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn local_address_port() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let local = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_local_address(Some(local));
+        connector.set_local_port_range(Some((addr.port(), addr.port())));
+        let stream = connector.connect_once(addr, None).unwrap();
+        assert_eq!(stream.local_addr().unwrap(), local);
+
+        let err = connector.connect_once(addr, None).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AddrInUse);
+    }
+
+    #[test]
+    fn builder() {
+        let connector = HttpTimeoutConnector::builder()
+            .connect_timeout(Duration::from_secs(1))
+            .total_timeout(Duration::from_secs(2))
+            .nodelay(true)
+            .build();
+        assert_eq!(connector.connect_timeout(), Some(Duration::from_secs(1)));
+        assert_eq!(connector.total_timeout(), Some(Duration::from_secs(2)));
+        assert_eq!(connector.resolve_timeout(), None);
+        assert!(connector.nodelay());
+    }
+
+    #[test]
+    fn connect_socket() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_nodelay(true);
+        let socket = connector.connect_socket("127.0.0.1", port).unwrap();
+        assert!(socket.nodelay().unwrap());
+        assert_eq!(socket.peer_addr().unwrap().as_inet().unwrap().port(), port);
+    }
+
+    #[test]
+    fn connect_cancellable() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let cancel = Arc::new(AtomicBool::new(false));
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.connect_cancellable("127.0.0.1", port, cancel.clone()).unwrap();
+
+        connector.set_resolver(Some(Box::new(|_, _| {
+            thread::sleep(Duration::from_secs(5));
+            Ok(vec![])
+        })));
+        let cancel2 = cancel.clone();
+        let t = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            cancel2.store(true, Ordering::SeqCst);
+        });
+        let start = Instant::now();
+        let err = connector.connect_cancellable("example.invalid", port, cancel).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Interrupted);
+        assert!(start.elapsed() < Duration::from_secs(1));
+        t.join().unwrap();
+    }
+
+    #[test]
+    fn connect_spawn() {
+        fn is_send_sync<T: Send + Sync>() {}
+        is_send_sync::<HttpTimeoutConnector>();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let connector = HttpTimeoutConnector::new();
+        let stream = connector.connect_spawn("127.0.0.1".to_owned(), port).join().unwrap().unwrap();
+        assert_eq!(stream.0.peer_addr().unwrap().port(), port);
+    }
+
+    #[test]
+    fn from_env() {
+        let vars = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                vars.iter().find(|&&(n, _)| n == name).map(|&(_, v)| OsString::from(v))
+            }
+        };
+
+        let connector = HttpTimeoutConnector::from_vars(vars(&[])).unwrap();
+        assert_eq!(connector.connect_timeout(), None);
+        assert_eq!(connector.total_timeout(), None);
+
+        let connector = HttpTimeoutConnector::from_vars(vars(&[("HTTP_CONNECT_TIMEOUT_MS", "250"),
+                                                               ("HTTP_RESOLVE_TIMEOUT_MS", ""),
+                                                               ("HTTP_TOTAL_TIMEOUT_MS", " 1000 "),
+                                                               ("HTTP_READ_TIMEOUT_MS", "0")]))
+            .unwrap();
+        assert_eq!(connector.connect_timeout(), Some(Duration::from_millis(250)));
+        assert_eq!(connector.resolve_timeout(), None);
+        assert_eq!(connector.total_timeout(), Some(Duration::from_secs(1)));
+        assert_eq!(connector.read_timeout(), Some(Duration::from_secs(0)));
+        assert_eq!(connector.write_timeout(), None);
+
+        let err = HttpTimeoutConnector::from_vars(vars(&[("HTTP_WRITE_TIMEOUT_MS", "5s")]))
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(err.to_string().contains("HTTP_WRITE_TIMEOUT_MS"));
+        assert!(err.to_string().contains("5s"));
+    }
+
+    #[test]
+    fn has_timeout() {
+        let mut connector = HttpTimeoutConnector::new();
+        assert!(!connector.has_timeout());
+        connector.set_write_timeout(Some(Duration::from_secs(1)));
+        assert!(connector.has_timeout());
+        connector.set_write_timeout(None);
+        connector.set_connect_timeout_for_scheme("https", Some(Duration::from_secs(1)));
+        assert!(connector.has_timeout());
+    }
+
+    #[test]
+    fn getters() {
+        let mut connector = HttpTimeoutConnector::new();
+        assert!(connector.resolver().is_none());
+        assert!(connector.address_sort().is_none());
+        assert!(connector.socket_configurator().is_none());
+        assert!(connector.connect_success_hook().is_none());
+        assert!(connector.connect_failure_hook().is_none());
+        assert!(connector.resolve_hook().is_none());
+        assert!(connector.address_filter().is_none());
+
+        let addr: SocketAddr = "127.0.0.1:80".parse().unwrap();
+        connector.set_resolver(Some(Box::new(move |_, _| Ok(vec![addr]))));
+        connector.set_address_sort(Some(Box::new(|a, b| a.cmp(b))));
+        connector.set_socket_configurator(Some(Box::new(|_| Ok(()))));
+        connector.set_connect_success_hook(Some(Box::new(|_, _| {})));
+        connector.set_connect_failure_hook(Some(Box::new(|_, _| {})));
+        connector.set_resolve_hook(Some(Box::new(|_, _| {})));
+        connector.set_address_filter(Some(Box::new(|_| true)));
+        assert_eq!(connector.resolver().unwrap()("example.invalid", 80).unwrap(), vec![addr]);
+        assert!(connector.address_sort().is_some());
+        assert!(connector.socket_configurator().is_some());
+        assert!(connector.connect_success_hook().is_some());
+        assert!(connector.connect_failure_hook().is_some());
+        assert!(connector.resolve_hook().is_some());
+        assert!(connector.address_filter().is_some());
+    }
+
+    #[test]
+    fn connect_detailed() {
+        let refused = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_resolver(Some(Box::new(move |_, _| {
+            thread::sleep(Duration::from_millis(50));
+            Ok(vec![refused, addr])
+        })));
+
+        for &happy_eyeballs in &[false, true] {
+            connector.set_happy_eyeballs(happy_eyeballs);
+            let (_, timing) = connector.connect_detailed("example.invalid", 80).unwrap();
+            assert!(timing.resolve() >= Duration::from_millis(50));
+            let addrs = timing.attempts().iter().map(|&(a, _)| a).collect::<Vec<_>>();
+            assert_eq!(addrs, vec![refused, addr]);
+            assert!(timing.attempts().iter().all(|&(_, t)| t <= timing.connect()));
+        }
+    }
+
+    #[test]
+    fn latency_percentiles() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut connector = HttpTimeoutConnector::new();
+        assert_eq!(connector.latency_percentiles().count(), 0);
+        connector.set_resolver(Some(Box::new(move |_, _| {
+            thread::sleep(Duration::from_millis(20));
+            Ok(vec![addr])
+        })));
+        connector.connect("example.invalid", 80, "http").unwrap();
+        connector.clone().connect("example.invalid", 80, "http").unwrap();
+        connector.connect_addrs(&[]).unwrap_err();
+
+        let stats = connector.latency_percentiles();
+        assert_eq!(stats.count(), 2);
+        assert!(stats.p50() >= Duration::from_millis(20));
+        assert!(stats.p50() <= stats.p90());
+        assert!(stats.p90() <= stats.p99());
+    }
+
+    #[test]
+    fn stats() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.connect("127.0.0.1", addr.port(), "http").unwrap();
+        connector.clone().connect_addr(addr).unwrap();
+
+        connector.set_connect_timeout(Some(Duration::from_secs(0)));
+        connector.connect("127.0.0.1", addr.port(), "http").unwrap_err();
+
+        connector.set_resolver(Some(Box::new(|_, _| {
+            Err(io::Error::new(io::ErrorKind::Other, "resolution failed"))
+        })));
+        connector.connect("example.invalid", 80, "http").unwrap_err();
+
+        let stats = connector.stats();
+        assert_eq!(stats.connects(), 4);
+        assert_eq!(stats.succeeded(), 2);
+        assert_eq!(stats.timed_out(), 1);
+        assert_eq!(stats.resolve_failures(), 1);
+    }
+
+    #[test]
+    fn clone() {
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_connect_timeout(Some(Duration::from_millis(250)));
+        connector.set_nodelay(true);
+
+        let clone = connector.clone();
+        assert_eq!(clone.connect_timeout(), Some(Duration::from_millis(250)));
+        assert!(clone.nodelay());
+    }
+
+    #[test]
+    fn refused() {
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+
+        let connector = HttpTimeoutConnector::new();
+        match connector.connect("127.0.0.1", port, "http") {
+            Ok(_) => panic!("unexpected success"),
+            Err(hyper::Error::Io(ref e)) if e.kind() == io::ErrorKind::ConnectionRefused => {
+                match e.get_ref().and_then(|e| e.downcast_ref::<ConnectError>()) {
+                    Some(&ConnectError::Refused(_)) => {}
+                    e => panic!("unexpected error {:?}", e),
+                }
+            }
+            Err(e) => panic!("unexpected error {:?}", e),
+        }
+    }
+
+    #[test]
+    fn watchdog_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_timeout_strategy(TimeoutStrategy::WatchdogThread);
+        connector.set_connect_timeout(Some(Duration::from_secs(5)));
+        connector.connect_addr(addr).unwrap();
+
+        let (addr, _sockets) = unresponsive();
+        connector.set_connect_timeout(Some(Duration::from_millis(100)));
+        let start = Instant::now();
+        let err = connector.connect_addr(addr).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    /// Returns the address of a listener whose accept queue is full, so that
+    /// connection attempts to it hang, along with the sockets which must be
+    /// kept open for it to remain so.
+    fn unresponsive() -> (SocketAddr, Vec<Socket>) {
+        let backlog = Socket::new(Domain::ipv4(), Type::stream(), None).unwrap();
+        backlog.bind(&SockAddr::from("127.0.0.1:0".parse::<SocketAddr>().unwrap())).unwrap();
+        backlog.listen(0).unwrap();
+        let addr = backlog.local_addr().unwrap().as_inet().unwrap().into();
+        let mut sockets = vec![backlog];
+        loop {
+            let socket = Socket::new(Domain::ipv4(), Type::stream(), None).unwrap();
+            match socket.connect_timeout(&SockAddr::from(addr), Duration::from_millis(100)) {
+                Ok(()) => sockets.push(socket),
+                Err(_) => break,
+            }
+            assert!(sockets.len() <= 16, "unable to fill accept queue");
+        }
+        (addr, sockets)
+    }
+
+    #[test]
+    fn parallel_attempts() {
+        let (unresponsive, _sockets) = unresponsive();
+        let refused = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_connect_timeout(Some(Duration::from_secs(10)));
+        connector.set_parallel_attempts(2);
+        let addrs = vec![unresponsive, refused, addr];
+        connector.set_resolver(Some(Box::new(move |_, _| Ok(addrs.clone()))));
+
+        let start = Instant::now();
+        let (_, info) = connector.connect_with_info("example.invalid", 80, "http").unwrap();
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(info.addr(), addr);
+        assert_eq!(info.index(), 2);
+        assert_eq!(info.failed_attempts(), 1);
+    }
+
+    #[test]
+    fn deadline_exceeded() {
+        let (addr, _sockets) = unresponsive();
+        let is_deadline = |e: &io::Error| {
+            match e.get_ref().and_then(|e| e.downcast_ref::<ConnectError>()) {
+                Some(&ConnectError::DeadlineExceeded(_)) => true,
+                Some(&ConnectError::Timeout(_)) => false,
+                e => panic!("unexpected error {:?}", e),
+            }
+        };
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_connect_timeout(Some(Duration::from_millis(50)));
+        connector.set_total_timeout(Some(Duration::from_secs(5)));
+        let err = connector.connect_addr(addr).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert!(!is_deadline(&err));
+        assert!(!err.to_string().contains("deadline"));
+
+        connector.set_connect_timeout(Some(Duration::from_secs(5)));
+        connector.set_total_timeout(Some(Duration::from_millis(50)));
+        let err = connector.connect_addr(addr).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert!(is_deadline(&err));
+        assert!(err.to_string().contains("overall connect deadline exceeded"));
+
+        let err = connector.connect_addrs(&[addr, addr]).unwrap_err();
+        assert!(is_deadline(&err));
+
+        connector.set_resolver(Some(Box::new(move |_, _| Ok(vec![addr]))));
+        let err = connector.connect_timed("example.invalid", 80, "http").unwrap_err();
+        assert!(is_deadline(&err));
+
+        let err = connector.connect_with_deadline("example.invalid", 80, Instant::now())
+            .unwrap_err();
+        assert!(is_deadline(&err));
+    }
+
+    #[test]
+    fn zero_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_connect_timeout(Some(Duration::from_secs(0)));
+        let err = connector.connect_addr(addr).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert_eq!(err.to_string(), format!("connection to {} timed out after 0ns", addr));
+
+        match connector.connect("127.0.0.1", addr.port(), "http") {
+            Err(hyper::Error::Io(ref e)) if e.kind() == io::ErrorKind::TimedOut => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn invalid_host() {
+        let connector = HttpTimeoutConnector::new();
+        for &(host, msg) in &[("", "host is empty"),
+                              ("http://example.com",
+                               "host `http://example.com` must not include a URL scheme"),
+                              ("example .com", "host `example .com` contains whitespace"),
+                              ("example.com\n", "host `example.com\n` contains whitespace")] {
+            match connector.connect_host(host, 80, connector.connect_timeout, None) {
+                Err(ConnectError::Other(ref e)) => {
+                    assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+                    assert_eq!(e.to_string(), msg);
+                }
+                r => panic!("unexpected result for {:?}: {:?}", host, r),
+            }
+        }
+    }
+
+    #[test]
+    fn no_addresses() {
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_resolver(Some(Box::new(|_, _| Ok(vec![]))));
+        match connector.connect_host("example.invalid", 80, connector.connect_timeout, None) {
+            Err(ConnectError::NoAddresses(ref e)) => {
+                assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+                assert!(e.to_string().contains("example.invalid"));
+            }
+            r => panic!("unexpected result {:?}", r),
+        }
+
+        connector.set_resolver(Some(Box::new(|_, port| {
+            Ok(vec![SocketAddr::new("::1".parse().unwrap(), port)])
+        })));
+        connector.set_address_family(AddressFamily::Ipv4Only);
+        match connector.connect("example.invalid", 80, "http") {
+            Err(hyper::Error::Io(ref e)) => {
+                match e.get_ref().and_then(|e| e.downcast_ref::<ConnectError>()) {
+                    Some(&ConnectError::NoAddresses(_)) => {}
+                    e => panic!("unexpected error {:?}", e),
+                }
+            }
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn attempt_errors() {
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let addrs = vec![SocketAddr::new("127.0.0.1".parse().unwrap(), port),
+                         SocketAddr::new("127.0.0.1".parse().unwrap(), port)];
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_local_address(Some("[::1]:0".parse().unwrap()));
+
+        let err = connector.connect_sequential(addrs.clone(), None, None).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        let errors = err.get_ref().unwrap().downcast_ref::<AttemptErrors>().unwrap().errors();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].0, addrs[0]);
+        assert!(err.to_string().starts_with("failed to connect to any address"));
+    }
+
+    #[test]
+    fn ttl() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_ttl(Some(17));
+        let stream = connector.connect_once(addr, None).unwrap();
+        assert_eq!(stream.ttl().unwrap(), 17);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn tos() {
+        use libc::{self, c_void, socklen_t};
+        use std::mem;
+        use std::os::raw::c_int;
+        use std::os::unix::io::AsRawFd;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_tos(Some(0x10));
+        let stream = connector.connect_once(addr, None).unwrap();
+        let mut tos: c_int = 0;
+        let mut len = mem::size_of::<c_int>() as socklen_t;
+        let r = unsafe {
+            libc::getsockopt(stream.as_raw_fd(),
+                             libc::IPPROTO_IP,
+                             libc::IP_TOS,
+                             &mut tos as *mut c_int as *mut c_void,
+                             &mut len)
+        };
+        assert_eq!(r, 0);
+        assert_eq!(tos, 0x10);
+
+        let listener = TcpListener::bind("[::1]:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        connector.connect_once(addr, None).unwrap();
+    }
+
+    #[test]
+    fn ignore_option_errors() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_congestion_control(Some("not-an-algorithm".to_owned()));
+        connector.connect_once(addr, None).unwrap_err();
+
+        connector.set_ignore_option_errors(true);
+        connector.connect_once(addr, None).unwrap();
+
+        connector.set_local_address(Some("[::1]:0".parse().unwrap()));
+        let err = connector.connect_once(addr, None).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn bind_device() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_bind_device(Some("nonexistent0".to_owned()));
+        let err = connector.connect_once(addr, None).unwrap_err();
+        if cfg!(target_os = "linux") {
+            // ENODEV, or EPERM without CAP_NET_RAW on older kernels
+            assert!(err.raw_os_error().is_some(), "{:?}", err);
+        } else {
+            assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+        }
+    }
+
+    #[test]
+    fn priority() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_priority(Some(4));
+        let r = connector.connect_once(addr, None);
+        if cfg!(target_os = "linux") {
+            r.unwrap();
+        } else {
+            assert_eq!(r.unwrap_err().kind(), io::ErrorKind::Unsupported);
+        }
+    }
+
+    #[test]
+    fn user_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_user_timeout(Some(Duration::from_secs(10)));
+        let r = connector.connect_once(addr, None);
+        if cfg!(target_os = "linux") {
+            r.unwrap();
+        } else {
+            assert_eq!(r.unwrap_err().kind(), io::ErrorKind::Unsupported);
+        }
+    }
+
+    #[test]
+    fn tcp_fastopen() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_tcp_fastopen(true);
+        let r = connector.connect_once(addr, None);
+        if cfg!(target_os = "linux") {
+            r.unwrap();
+        } else {
+            assert_eq!(r.unwrap_err().kind(), io::ErrorKind::Unsupported);
+        }
+    }
+
+    #[test]
+    fn freebind() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_freebind(true);
+        let r = connector.connect_once(addr, None);
+        if cfg!(target_os = "linux") {
+            r.unwrap();
+        } else {
+            assert_eq!(r.unwrap_err().kind(), io::ErrorKind::Unsupported);
+        }
+    }
+
+    #[test]
+    fn transparent() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_transparent(true);
+        let r = connector.connect_once(addr, None);
+        if cfg!(target_os = "linux") {
+            // CAP_NET_ADMIN is required
+            if let Err(e) = r {
+                assert_eq!(e.kind(), io::ErrorKind::PermissionDenied);
+            }
+        } else {
+            assert_eq!(r.unwrap_err().kind(), io::ErrorKind::Unsupported);
+        }
+    }
+
+    #[test]
+    fn reuse_port() {
+        let a = TcpListener::bind("127.0.0.1:0").unwrap();
+        let b = TcpListener::bind("127.0.0.1:0").unwrap();
+        let local = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_local_address(Some(local));
+        connector.set_reuse_port(true);
+        let r = connector.connect_once(a.local_addr().unwrap(), None);
+        if cfg!(windows) {
+            assert_eq!(r.unwrap_err().kind(), io::ErrorKind::Unsupported);
+            return;
+        }
+        let _a = r.unwrap();
+        let stream = connector.connect_once(b.local_addr().unwrap(), None).unwrap();
+        assert_eq!(stream.local_addr().unwrap(), local);
+    }
+
+    #[test]
+    fn mss() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_mss(Some(1000));
+        let r = connector.connect_once(addr, None);
+        if cfg!(windows) {
+            assert_eq!(r.unwrap_err().kind(), io::ErrorKind::Unsupported);
+        } else {
+            r.unwrap();
+        }
+    }
+
+    #[test]
+    fn congestion_control() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_congestion_control(Some("reno".to_owned()));
+        let r = connector.connect_once(addr, None);
+        if cfg!(target_os = "linux") {
+            r.unwrap();
+        } else {
+            assert_eq!(r.unwrap_err().kind(), io::ErrorKind::Unsupported);
+        }
+
+        connector.set_congestion_control(Some("not-an-algorithm".to_owned()));
+        let err = connector.connect_once(addr, None).unwrap_err();
+        if cfg!(target_os = "linux") {
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        } else {
+            assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+        }
+    }
+
+    #[test]
+    fn address_family() {
+        let v4: SocketAddr = "127.0.0.1:80".parse().unwrap();
+        let v6: SocketAddr = "[::1]:80".parse().unwrap();
+        let addrs = vec![v6, v4];
+
+        let mut connector = HttpTimeoutConnector::new();
+        assert_eq!(connector.order_addrs(addrs.clone()), vec![v6, v4]);
+
+        connector.set_address_family(AddressFamily::Ipv4Only);
+        assert_eq!(connector.order_addrs(addrs.clone()), vec![v4]);
+
+        connector.set_address_family(AddressFamily::Ipv6Only);
+        assert_eq!(connector.order_addrs(addrs.clone()), vec![v6]);
+
+        connector.set_address_family(AddressFamily::PreferIpv4);
+        assert_eq!(connector.order_addrs(addrs.clone()), vec![v4, v6]);
+
+        connector.set_address_family(AddressFamily::PreferIpv6);
+        assert_eq!(connector.order_addrs(vec![v4, v6]), vec![v6, v4]);
+    }
+
+    #[test]
+    fn disable_ipv6() {
+        let v4: SocketAddr = "127.0.0.1:80".parse().unwrap();
+        let v6: SocketAddr = "[::1]:80".parse().unwrap();
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_disable_ipv6(true);
+        connector.set_address_family(AddressFamily::PreferIpv6);
+        assert_eq!(connector.order_addrs(vec![v6, v4]), vec![v4]);
+
+        connector.set_resolver(Some(Box::new(move |_, _| Ok(vec![v6]))));
+        match connector.connect_host("example.invalid", 80, connector.connect_timeout, None) {
+            Err(ConnectError::NoAddresses(_)) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn address_sort() {
+        let a: SocketAddr = "10.0.0.1:80".parse().unwrap();
+        let b: SocketAddr = "192.168.0.1:80".parse().unwrap();
+        let c: SocketAddr = "192.168.0.2:80".parse().unwrap();
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_address_sort(Some(Box::new(|a, b| {
+            let local = |a: &SocketAddr| a.to_string().starts_with("192.168.");
+            local(b).cmp(&local(a))
+        })));
+        assert_eq!(connector.order_addrs(vec![a, b, c]), vec![b, c, a]);
+    }
+
+    #[test]
+    fn duplicate_addresses() {
+        let refused = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let failures = Arc::new(AtomicUsize::new(0));
+        let failures2 = failures.clone();
+        let mut connector = HttpTimeoutConnector::new();
+        let addrs = vec![refused, refused, addr, refused];
+        connector.set_resolver(Some(Box::new(move |_, _| Ok(addrs.clone()))));
+        connector.set_connect_failure_hook(Some(Box::new(move |_, _| {
+            failures2.fetch_add(1, Ordering::SeqCst);
+        })));
+        assert_eq!(connector.resolve("example.invalid", 80).unwrap(), vec![refused, addr]);
+
+        let (_, info) = connector.connect_with_info("example.invalid", 80, "http").unwrap();
+        assert_eq!(info.addr(), addr);
+        assert_eq!(info.addresses(), 2);
+        assert_eq!(failures.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn shuffle_addresses() {
+        let addrs = (0..8)
+            .map(|_| TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap())
+            .collect::<Vec<_>>();
+
+        let attempted = Arc::new(Mutex::new(vec![]));
+        let attempted2 = attempted.clone();
+        let mut connector = HttpTimeoutConnector::new();
+        let addrs2 = addrs.clone();
+        connector.set_resolver(Some(Box::new(move |_, _| Ok(addrs2.clone()))));
+        connector.set_connect_failure_hook(Some(Box::new(move |addr, _| {
+            attempted2.lock().unwrap().push(addr);
+        })));
+
+        connector.connect("example.invalid", 80, "http").unwrap_err();
+        assert_eq!(*attempted.lock().unwrap(), addrs);
+
+        connector.set_shuffle_addresses(true);
+        let mut sorted = addrs.clone();
+        sorted.sort();
+        let mut reordered = false;
+        for _ in 0..10 {
+            attempted.lock().unwrap().clear();
+            connector.connect("example.invalid", 80, "http").unwrap_err();
+            let mut attempted = attempted.lock().unwrap().clone();
+            reordered |= attempted != addrs;
+            attempted.sort();
+            assert_eq!(attempted, sorted);
+        }
+        assert!(reordered);
+    }
+
+    #[test]
+    fn address_cooldown() {
+        let refused = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_address_cooldown(Some(Duration::from_millis(100)));
+        connector.set_resolver(Some(Box::new(move |_, _| Ok(vec![refused, addr]))));
+
+        let (_, info) = connector.connect_with_info("example.invalid", 80, "http").unwrap();
+        assert_eq!(info.failed_attempts(), 1);
+        assert_eq!(connector.resolve("example.invalid", 80).unwrap(), vec![addr, refused]);
+        let (_, info) = connector.clone().connect_with_info("example.invalid", 80, "http").unwrap();
+        assert_eq!(info.failed_attempts(), 0);
+
+        // the limit applies after cooling down addresses are moved to the back
+        connector.set_max_attempts(Some(1));
+        let (_, info) = connector.connect_with_info("example.invalid", 80, "http").unwrap();
+        assert_eq!(info.addr(), addr);
+        connector.set_max_attempts(None);
+
+        thread::sleep(Duration::from_millis(150));
+        assert_eq!(connector.resolve("example.invalid", 80).unwrap(), vec![refused, addr]);
+    }
+
+    #[test]
+    fn sticky_addresses() {
+        let a = TcpListener::bind("127.0.0.1:0").unwrap();
+        let a_addr = a.local_addr().unwrap();
+        let b = TcpListener::bind("127.0.0.1:0").unwrap();
+        let b_addr = b.local_addr().unwrap();
+        let refused = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_sticky_addresses(true);
+        connector.set_resolver(Some(Box::new(move |_, _| Ok(vec![refused, a_addr, b_addr]))));
+        assert_eq!(connector.resolve("example.invalid", 80).unwrap(),
+                   vec![refused, a_addr, b_addr]);
+
+        let (_, info) = connector.connect_with_info("example.invalid", 80, "http").unwrap();
+        assert_eq!(info.addr(), a_addr);
+        assert_eq!(connector.resolve("example.invalid", 80).unwrap(),
+                   vec![a_addr, refused, b_addr]);
+        assert_eq!(connector.resolve("EXAMPLE.invalid", 80).unwrap(),
+                   vec![a_addr, refused, b_addr]);
+        let (_, info) = connector.connect_with_info("example.invalid", 80, "http").unwrap();
+        assert_eq!(info.failed_attempts(), 0);
+
+        connector.set_max_attempts(Some(1));
+        let (_, info) = connector.connect_with_info("example.invalid", 80, "http").unwrap();
+        assert_eq!(info.addr(), a_addr);
+        connector.set_max_attempts(None);
+
+        drop(a);
+        let (_, info) = connector.connect_with_info("example.invalid", 80, "http").unwrap();
+        assert_eq!(info.addr(), b_addr);
+        assert_eq!(connector.resolve("example.invalid", 80).unwrap(),
+                   vec![b_addr, refused, a_addr]);
+
+        drop(b);
+        connector.connect("example.invalid", 80, "http").unwrap_err();
+        assert_eq!(connector.resolve("example.invalid", 80).unwrap(),
+                   vec![refused, a_addr, b_addr]);
+    }
+
+    #[test]
+    fn resolve_hook() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let other: SocketAddr = "192.0.2.1:80".parse().unwrap();
+        let resolved = Arc::new(Mutex::new(vec![]));
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_dns_cache_ttl(Some(Duration::from_secs(60)));
+        connector.set_resolver(Some(Box::new(move |host, _| {
+            match host {
+                "empty.invalid" => Ok(vec![]),
+                "fail.invalid" => Err(io::Error::new(io::ErrorKind::Other, "fail")),
+                _ => Ok(vec![addr, other]),
+            }
+        })));
+        let resolved2 = resolved.clone();
+        connector.set_resolve_hook(Some(Box::new(move |host, addrs| {
+            resolved2.lock().unwrap().push((host.to_owned(), addrs.to_vec()));
+        })));
+
+        connector.connect("example.invalid", 80, "http").unwrap();
+        connector.connect("example.invalid", 80, "http").unwrap();
+        connector.connect("empty.invalid", 80, "http").unwrap_err();
+        connector.connect("fail.invalid", 80, "http").unwrap_err();
+        connector.resolve("example.invalid", 80).unwrap();
+
+        let resolved = resolved.lock().unwrap();
+        assert_eq!(*resolved,
+                   vec![("example.invalid".to_owned(), vec![addr, other]),
+                        ("example.invalid".to_owned(), vec![addr, other]),
+                        ("empty.invalid".to_owned(), vec![])]);
+    }
+
+    #[test]
+    fn address_filter() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let blocked: SocketAddr = "10.0.0.1:80".parse().unwrap();
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_address_filter(Some(Box::new(move |a| *a != blocked)));
+        connector.set_resolver(Some(Box::new(move |host, _| {
+            match host {
+                "blocked.invalid" => Ok(vec![blocked]),
+                _ => Ok(vec![blocked, addr]),
+            }
+        })));
+
+        assert_eq!(connector.resolve("example.invalid", 80).unwrap(), vec![addr]);
+        let (_, info) = connector.connect_with_info("example.invalid", 80, "http").unwrap();
+        assert_eq!(info.addr(), addr);
+        assert_eq!(info.failed_attempts(), 0);
+
+        connector.set_max_attempts(Some(1));
+        assert_eq!(connector.resolve("example.invalid", 80).unwrap(), vec![addr]);
+        let (_, info) = connector.connect_with_info("example.invalid", 80, "http").unwrap();
+        assert_eq!(info.addr(), addr);
+        connector.set_max_attempts(None);
+
+        let err = match connector.connect("blocked.invalid", 80, "http") {
+            Err(hyper::Error::Io(e)) => e,
+            r => panic!("unexpected result {:?}", r.map(|_| ())),
+        };
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        match err.get_ref().and_then(|e| e.downcast_ref::<ConnectError>()) {
+            Some(&ConnectError::NoAddresses(_)) => {}
+            e => panic!("unexpected error {:?}", e),
+        }
+        assert!(err.to_string().contains("blocked.invalid"));
+        let err = connector.resolve("blocked.invalid", 80).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+    }
+
+    #[test]
+    fn private_addresses() {
+        for ip in &["10.1.2.3",
+                    "172.16.0.1",
+                    "172.31.255.255",
+                    "192.168.1.1",
+                    "127.0.0.1",
+                    "127.1.2.3",
+                    "169.254.169.254",
+                    "0.0.0.0",
+                    "::1",
+                    "::",
+                    "fe80::1",
+                    "fc00::1",
+                    "fd12:3456::1",
+                    "::ffff:127.0.0.1",
+                    "::ffff:10.0.0.1"] {
+            assert!(is_private(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in &["8.8.8.8",
+                    "172.32.0.1",
+                    "192.0.2.1",
+                    "2001:db8::1",
+                    "fec0::1",
+                    "::ffff:8.8.8.8",
+                    "64:ff9b::1"] {
+            assert!(!is_private(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+
+    #[test]
+    fn block_private_addresses() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let public: SocketAddr = "192.0.2.1:80".parse().unwrap();
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_block_private_addresses(true);
+        connector.set_resolver(Some(Box::new(move |host, _| {
+            match host {
+                "mixed.invalid" => Ok(vec![addr, public]),
+                _ => Ok(vec![addr, "[::ffff:10.0.0.1]:80".parse().unwrap()]),
+            }
+        })));
+
+        assert_eq!(connector.resolve("mixed.invalid", 80).unwrap(), vec![public]);
+        connector.set_max_attempts(Some(1));
+        assert_eq!(connector.resolve("mixed.invalid", 80).unwrap(), vec![public]);
+        connector.set_max_attempts(None);
+        let err = match connector.connect("example.invalid", 80, "http") {
+            Err(hyper::Error::Io(e)) => e,
+            r => panic!("unexpected result {:?}", r.map(|_| ())),
+        };
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        match err.get_ref().and_then(|e| e.downcast_ref::<ConnectError>()) {
+            Some(&ConnectError::BlockedAddress(_)) => {}
+            e => panic!("unexpected error {:?}", e),
+        }
+        assert!(err.to_string().contains("example.invalid"));
+
+        connector.set_block_private_addresses(false);
+        connector.connect("example.invalid", 80, "http").unwrap();
+    }
+
+    #[test]
+    fn host_override() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let lookups = Arc::new(AtomicUsize::new(0));
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_dns_cache_ttl(Some(Duration::from_secs(60)));
+        let lookups2 = lookups.clone();
+        connector.set_resolver(Some(Box::new(move |_, _| {
+            lookups2.fetch_add(1, Ordering::SeqCst);
+            Ok(vec!["192.0.2.1:80".parse().unwrap()])
+        })));
+        connector.resolve("example.invalid", addr.port()).unwrap();
+        assert_eq!(lookups.load(Ordering::SeqCst), 1);
+
+        connector.add_host_override("Example.Invalid".to_owned(), vec![addr.ip()]);
+        assert_eq!(connector.host_override("example.invalid"), Some(&[addr.ip()][..]));
+        let (_, info) =
+            connector.connect_with_info("example.invalid", addr.port(), "http").unwrap();
+        assert_eq!(info.addr(), addr);
+        assert!(!info.resolved_from_cache());
+        assert_eq!(lookups.load(Ordering::SeqCst), 1);
+
+        connector.remove_host_override("EXAMPLE.invalid");
+        assert_eq!(connector.host_override("example.invalid"), None);
+        assert_ne!(connector.resolve("example.invalid", addr.port()).unwrap(), vec![addr]);
+    }
+
+    #[test]
+    fn prewarm() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let lookups = Arc::new(AtomicUsize::new(0));
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_dns_cache_ttl(Some(Duration::from_secs(60)));
+        let lookups2 = lookups.clone();
+        connector.set_resolver(Some(Box::new(move |host, _| {
+            lookups2.fetch_add(1, Ordering::SeqCst);
+            match host {
+                "empty.invalid" => Ok(vec![]),
+                "fail.invalid" => Err(io::Error::new(io::ErrorKind::Other, "fail")),
+                _ => Ok(vec![addr]),
+            }
+        })));
+
+        let hosts = [("example.invalid", 80), ("empty.invalid", 80), ("fail.invalid", 80)];
+        let results = connector.prewarm(&hosts);
+        assert_eq!(results.len(), 3);
+        results[0].as_ref().unwrap();
+        assert_eq!(results[1].as_ref().unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(results[2].as_ref().unwrap_err().kind(), io::ErrorKind::Other);
+        assert_eq!(lookups.load(Ordering::SeqCst), 3);
+
+        connector.prewarm(&hosts[..1])[0].as_ref().unwrap();
+        let (_, info) = connector.connect_with_info("example.invalid", 80, "http").unwrap();
+        assert!(info.resolved_from_cache());
+        assert_eq!(lookups.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn host_override_client() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let t = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = vec![];
+            while !request.ends_with(b"\r\n\r\n") {
+                let mut byte = [0];
+                stream.read_exact(&mut byte).unwrap();
+                request.push(byte[0]);
+            }
+            stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n").unwrap();
+            String::from_utf8(request).unwrap()
+        });
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.add_host_override("service.internal".to_owned(),
+                                    vec!["127.0.0.1".parse().unwrap()]);
+        let client = Client::with_connector(connector);
+        let response = client.get(&format!("http://service.internal:{}/", port)).send().unwrap();
+        assert_eq!(response.status, hyper::Ok);
+
+        let request = t.join().unwrap();
+        assert!(request.contains(&format!("Host: service.internal:{}\r\n", port)),
+                "{}",
+                request);
+    }
+
+    #[test]
+    fn reset() {
+        let refused = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let lookups = Arc::new(AtomicUsize::new(0));
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_dns_cache_ttl(Some(Duration::from_secs(60)));
+        connector.set_address_cooldown(Some(Duration::from_secs(60)));
+        connector.set_sticky_addresses(true);
+        let lookups2 = lookups.clone();
+        connector.set_resolver(Some(Box::new(move |_, _| {
+            lookups2.fetch_add(1, Ordering::SeqCst);
+            Ok(vec![refused, addr])
+        })));
+
+        connector.connect("example.invalid", 80, "http").unwrap();
+        let plan = connector.connection_plan("example.invalid", 80).unwrap();
+        assert!(plan.resolved_from_cache());
+        assert!(plan.addresses()[0].sticky());
+        assert!(plan.addresses()[1].cooling_down());
+        assert_eq!(lookups.load(Ordering::SeqCst), 1);
+
+        connector.clone().reset();
+        let plan = connector.connection_plan("example.invalid", 80).unwrap();
+        assert!(!plan.resolved_from_cache());
+        assert!(plan.addresses().iter().all(|a| !a.sticky() && !a.cooling_down()));
+        assert_eq!(plan.addresses()[0].addr(), refused);
+        assert_eq!(lookups.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn dry_run() {
+        let sockets = Arc::new(AtomicUsize::new(0));
+        let sockets2 = sockets.clone();
+        let a: SocketAddr = "192.0.2.1:80".parse().unwrap();
+        let b: SocketAddr = "[2001:db8::1]:80".parse().unwrap();
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_address_family(AddressFamily::PreferIpv6);
+        connector.set_sticky_addresses(true);
+        connector.set_address_cooldown(Some(Duration::from_secs(60)));
+        connector.set_resolver(Some(Box::new(move |_, _| Ok(vec![a, b]))));
+        connector.set_socket_configurator(Some(Box::new(move |_| {
+            sockets2.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        })));
+
+        assert_eq!(connector.resolve("example.invalid", 80).unwrap(), vec![b, a]);
+        let plan = connector.connection_plan("example.invalid", 80).unwrap();
+        assert_eq!(plan.addresses().len(), 2);
+        assert_eq!(sockets.load(Ordering::SeqCst), 0);
+        assert_eq!(connector.stats().connects(), 0);
+    }
+
+    #[test]
+    fn resolve() {
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_address_family(AddressFamily::PreferIpv4);
+        connector.set_resolver(Some(Box::new(|_, port| {
+            Ok(vec![SocketAddr::new("::1".parse().unwrap(), port),
+                    SocketAddr::new("127.0.0.1".parse().unwrap(), port)])
+        })));
+
+        let addrs = connector.resolve("example.invalid", 80).unwrap();
+        assert_eq!(addrs,
+                   vec!["127.0.0.1:80".parse().unwrap(), "[::1]:80".parse().unwrap()]);
+    }
+
+    #[test]
+    fn max_attempts() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (dead, _sockets) = unresponsive();
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_connect_timeout(Some(Duration::from_millis(100)));
+        connector.set_max_attempts(Some(1));
+        connector.set_resolver(Some(Box::new(move |_, _| Ok(vec![dead, addr]))));
+        let err = connector.connect_with_info("example.invalid", 80, "http").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+
+        connector.set_max_attempts(Some(2));
+        let (_, info) = connector.connect_with_info("example.invalid", 80, "http").unwrap();
+        assert_eq!(info.addr(), addr);
+        assert_eq!(info.addresses(), 2);
+        assert_eq!(info.failed_attempts(), 1);
+    }
+
+    #[test]
+    fn resolver() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_resolver(Some(Box::new(move |host, port| {
+            assert_eq!(host, "example.invalid");
+            assert_eq!(port, 1234);
+            Ok(vec![addr])
+        })));
+        connector.connect("example.invalid", 1234, "http").unwrap();
+
+        connector.set_resolve_timeout(Some(Duration::from_secs(5)));
+        connector.connect("example.invalid", 1234, "http").unwrap();
+    }
+
+    #[test]
+    fn dns_cache() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let lookups = Arc::new(AtomicUsize::new(0));
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_dns_cache_ttl(Some(Duration::from_secs(60)));
+        let lookups2 = lookups.clone();
+        connector.set_resolver(Some(Box::new(move |_, _| {
+            lookups2.fetch_add(1, Ordering::SeqCst);
+            Ok(vec![addr])
+        })));
+
+        let (_, info) = connector.connect_with_info("example.invalid", 1234, "http").unwrap();
+        assert!(!info.resolved_from_cache());
+        let (_, info) =
+            connector.clone().connect_with_info("example.invalid", 1234, "http").unwrap();
+        assert!(info.resolved_from_cache());
+        let (_, info) = connector.connect_with_info("EXAMPLE.invalid", 1234, "http").unwrap();
+        assert!(info.resolved_from_cache());
+        assert_eq!(lookups.load(Ordering::SeqCst), 1);
+
+        connector.connect("example.invalid", 1235, "http").unwrap();
+        assert_eq!(lookups.load(Ordering::SeqCst), 2);
+
+        let stats = connector.stats();
+        assert_eq!(stats.dns_cache_hits(), 2);
+        assert_eq!(stats.dns_cache_misses(), 2);
+    }
+
+    #[test]
+    fn read_write_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_read_timeout(Some(Duration::from_secs(1)));
+        connector.set_write_timeout(Some(Duration::from_secs(2)));
+
+        let stream = connector.connect("127.0.0.1", port, "http").unwrap();
+        assert_eq!(stream.0.read_timeout().unwrap(), Some(Duration::from_secs(1)));
+        assert_eq!(stream.0.write_timeout().unwrap(), Some(Duration::from_secs(2)));
+
+        connector.set_happy_eyeballs(true);
+        let stream = connector.connect("127.0.0.1", port, "http").unwrap();
+        assert_eq!(stream.0.read_timeout().unwrap(), Some(Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn handshake_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_read_timeout(Some(Duration::from_secs(1)));
+        connector.set_handshake_timeout(Some(Duration::from_secs(5)));
+
+        let stream = connector.connect("127.0.0.1", port, "http").unwrap();
+        assert_eq!(stream.0.read_timeout().unwrap(), Some(Duration::from_secs(5)));
+        assert_eq!(stream.0.write_timeout().unwrap(), Some(Duration::from_secs(5)));
+
+        connector.clear_handshake_timeout(&stream.0).unwrap();
+        assert_eq!(stream.0.read_timeout().unwrap(), Some(Duration::from_secs(1)));
+        assert_eq!(stream.0.write_timeout().unwrap(), None);
+    }
+
+    #[test]
+    fn nonblocking_stream() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_nonblocking_stream(true);
+        let mut stream = connector.connect("127.0.0.1", addr.port(), "http").unwrap();
+        let err = stream.0.read(&mut [0]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+
+        let mut stream = connector.connect_addr(addr).unwrap();
+        let err = stream.0.read(&mut [0]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+    }
+
+    #[test]
+    fn linger() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_linger(Some(Some(Duration::from_secs(0))));
+        let stream = connector.connect_once(addr, None).unwrap();
+        let socket = Socket::from(stream);
+        assert_eq!(socket.linger().unwrap(), Some(Duration::from_secs(0)));
+    }
+
+    #[test]
+    fn buffer_sizes() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_send_buffer_size(Some(64 * 1024));
+        connector.set_recv_buffer_size(Some(32 * 1024));
+        let socket = Socket::from(connector.connect_once(addr, None).unwrap());
+        // Linux doubles the requested size to account for bookkeeping overhead
+        assert!(socket.send_buffer_size().unwrap() >= 64 * 1024);
+        assert!(socket.recv_buffer_size().unwrap() >= 32 * 1024);
+    }
+
+    #[test]
+    fn retries() {
+        let addr = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let t = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            let listener = TcpListener::bind(addr).unwrap();
+            listener.accept().unwrap();
+        });
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_retries(20);
+        connector.set_retry_backoff(Duration::from_millis(50));
+        connector.connect("127.0.0.1", addr.port(), "http").unwrap();
+        t.join().unwrap();
+
+        connector.set_retries(0);
+        assert!(connector.connect("127.0.0.1", addr.port(), "http").is_err());
+    }
+
+    #[test]
+    fn per_address_retries() {
+        let a = TcpListener::bind("127.0.0.1:0").unwrap();
+        let a_addr = a.local_addr().unwrap();
+        let b = TcpListener::bind("127.0.0.1:0").unwrap();
+        let b_addr = b.local_addr().unwrap();
+        let refusals = Arc::new(Mutex::new(0));
+        let failures = Arc::new(AtomicUsize::new(0));
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_per_address_retries(2);
+        connector.set_resolver(Some(Box::new(move |_, _| Ok(vec![a_addr, b_addr]))));
+        let refusals2 = refusals.clone();
+        connector.set_socket_configurator(Some(Box::new(move |_| {
+            let mut refusals = refusals2.lock().unwrap();
+            if *refusals == 0 {
+                return Ok(());
+            }
+            *refusals -= 1;
+            Err(io::Error::new(io::ErrorKind::ConnectionRefused, "refused"))
+        })));
+        let failures2 = failures.clone();
+        connector.set_connect_failure_hook(Some(Box::new(move |_, _| {
+            failures2.fetch_add(1, Ordering::SeqCst);
+        })));
+
+        *refusals.lock().unwrap() = 2;
+        let (_, info) = connector.connect_with_info("example.invalid", 80, "http").unwrap();
+        assert_eq!(info.addr(), a_addr);
+        assert_eq!(info.failed_attempts(), 0);
+        assert_eq!(failures.load(Ordering::SeqCst), 2);
+
+        *refusals.lock().unwrap() = 3;
+        let (_, info) = connector.connect_with_info("example.invalid", 80, "http").unwrap();
+        assert_eq!(info.addr(), b_addr);
+        assert_eq!(info.failed_attempts(), 1);
+        assert_eq!(failures.load(Ordering::SeqCst), 5);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn resource_exhausted() {
+        let a = TcpListener::bind("127.0.0.1:0").unwrap();
+        let a_addr = a.local_addr().unwrap();
+        let b = TcpListener::bind("127.0.0.1:0").unwrap();
+        let b_addr = b.local_addr().unwrap();
+        let sockets = Arc::new(AtomicUsize::new(0));
+
+        for &happy_eyeballs in &[false, true] {
+            let mut connector = HttpTimeoutConnector::new();
+            connector.set_happy_eyeballs(happy_eyeballs);
+            connector.set_retries(3);
+            connector.set_resolver(Some(Box::new(move |_, _| Ok(vec![a_addr, b_addr]))));
+            let sockets2 = sockets.clone();
+            connector.set_socket_configurator(Some(Box::new(move |_| {
+                sockets2.fetch_add(1, Ordering::SeqCst);
+                Err(io::Error::from_raw_os_error(libc::EMFILE))
+            })));
+
+            let err = match connector.connect("example.invalid", 80, "http") {
+                Err(hyper::Error::Io(e)) => e,
+                r => panic!("unexpected result {:?}", r.map(|_| ())),
+            };
+            match err.get_ref().and_then(|e| e.downcast_ref::<ConnectError>()) {
+                Some(&ConnectError::ResourceExhausted(_)) => {}
+                e => panic!("unexpected error {:?}", e),
+            }
+            assert!(err.to_string().contains("open file limit"));
+            assert_eq!(sockets.swap(0, Ordering::SeqCst), 1);
+        }
+    }
+
+    #[test]
+    fn max_concurrent_connects() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (tx, rx) = mpsc::channel();
+        let rx = Mutex::new(rx);
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_max_concurrent_connects(Some(1));
+        connector.set_connect_queue_timeout(Some(Duration::from_millis(50)));
+        let connector2 = connector.clone();
+        connector.set_socket_configurator(Some(Box::new(move |_| {
+            rx.lock().unwrap().recv().unwrap();
+            Ok(())
+        })));
+        let t = thread::spawn(move || connector.connect_addr(addr).unwrap());
+
+        thread::sleep(Duration::from_millis(50));
+        let err = connector2.connect_addr(addr).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+
+        tx.send(()).unwrap();
+        t.join().unwrap();
+        connector2.connect_addr(addr).unwrap();
+    }
+
+    #[test]
+    fn retry_jitter() {
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_retry_backoff(Duration::from_millis(100));
+        assert_eq!(connector.retry_delay(), Duration::from_millis(100));
+
+        connector.set_retry_jitter(0.2);
+        let delays = (0..100).map(|_| connector.retry_delay()).collect::<Vec<_>>();
+        for &delay in &delays {
+            assert!(delay >= Duration::from_millis(80) && delay <= Duration::from_millis(120));
+        }
+        assert!(delays.iter().any(|&d| d != delays[0]));
+
+        connector.set_retry_jitter(5.0);
+        assert_eq!(connector.retry_jitter(), 1.0);
+        connector.set_retry_jitter(f64::INFINITY);
+        assert_eq!(connector.retry_jitter(), 1.0);
+        connector.set_retry_jitter(f64::NEG_INFINITY);
+        assert_eq!(connector.retry_jitter(), 0.0);
+
+        connector.set_retry_jitter(f64::NAN);
+        assert_eq!(connector.retry_jitter(), 0.0);
+        assert_eq!(connector.retry_delay(), Duration::from_millis(100));
+    }
+
+    #[test]
+    fn socket_configurator() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_socket_configurator(Some(Box::new(|socket| socket.set_nodelay(true))));
+        let stream = connector.connect_once(addr, None).unwrap();
+        assert!(stream.nodelay().unwrap());
+
+        connector.set_socket_configurator(Some(Box::new(|_| {
+            Err(io::Error::new(io::ErrorKind::Other, "nope"))
+        })));
+        let err = connector.connect_once(addr, None).unwrap_err();
+        assert_eq!(err.to_string(), "nope");
+    }
+
+    #[test]
+    fn connect_with_deadline() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let connector = HttpTimeoutConnector::new();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        connector.connect_with_deadline("127.0.0.1", port, deadline).unwrap();
+
+        let err = connector.connect_with_deadline("127.0.0.1", port, Instant::now()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    }
+
+    #[test]
+    fn hooks() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let good = listener.local_addr().unwrap();
+        let bad = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let successes = Arc::new(Mutex::new(vec![]));
+        let failures = Arc::new(Mutex::new(vec![]));
+
+        let mut connector = HttpTimeoutConnector::new();
+        let successes2 = successes.clone();
+        connector.set_connect_success_hook(Some(Box::new(move |addr, _| {
+            successes2.lock().unwrap().push(addr);
+        })));
+        let failures2 = failures.clone();
+        connector.set_connect_failure_hook(Some(Box::new(move |addr, e| {
+            failures2.lock().unwrap().push((addr, e.kind()));
+        })));
+
+        connector.connect_sequential(vec![bad, good], connector.connect_timeout, None).unwrap();
+        assert_eq!(*successes.lock().unwrap(), vec![good]);
+        assert_eq!(*failures.lock().unwrap(),
+                   vec![(bad, io::ErrorKind::ConnectionRefused)]);
+    }
+
+    #[test]
+    fn scope_id() {
+        let local = "[fe80::1]:0".parse().unwrap();
+        let remote = SocketAddr::V6(SocketAddrV6::new("fe80::2".parse().unwrap(), 80, 0, 3));
+        match local_bind_addr(local, &remote) {
+            SocketAddr::V6(addr) => assert_eq!(addr.scope_id(), 3),
+            addr => panic!("unexpected address {}", addr),
+        }
+
+        let local = "[::1]:0".parse().unwrap();
+        assert_eq!(local_bind_addr(local, &remote), local);
+
+        let local = SocketAddr::V6(SocketAddrV6::new("fe80::1".parse().unwrap(), 1234, 0, 5));
+        assert_eq!(local_bind_addr(local, &remote), local);
+
+        let listener = TcpListener::bind("[::1]:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let remote = SocketAddr::V6(SocketAddrV6::new("::1".parse().unwrap(), port, 0, 1));
+        HttpTimeoutConnector::new().connect_once(remote, None).unwrap();
+    }
+
+    #[test]
+    fn connect_addr() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_nodelay(true);
+        let stream = connector.connect_addr(addr).unwrap();
+        assert!(stream.0.nodelay().unwrap());
+
+        drop(listener);
+        let err = connector.connect_addr(addr).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused);
+    }
+
+    #[test]
+    fn connect_addrs() {
+        let refused = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let connector = HttpTimeoutConnector::new();
+        let stream = connector.connect_addrs(&[refused, addr]).unwrap();
+        assert_eq!(stream.0.peer_addr().unwrap(), addr);
+
+        let err = connector.connect_addrs(&[refused]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused);
+
+        let err = connector.connect_addrs(&[]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(connector.stats().connects(), 3);
+    }
+
+    #[test]
+    fn connect_timed() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let connector = HttpTimeoutConnector::new();
+        let start = Instant::now();
+        let (stream, elapsed) = connector.connect_timed("127.0.0.1", port, "http").unwrap();
+        assert_eq!(stream.0.peer_addr().unwrap().port(), port);
+        assert!(elapsed <= start.elapsed());
+
+        let err = connector.connect_timed("127.0.0.1", port, "https").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn probe() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let connector = HttpTimeoutConnector::new();
+        let start = Instant::now();
+        let elapsed = connector.probe("127.0.0.1", port).unwrap();
+        assert!(elapsed <= start.elapsed());
+
+        let (mut stream, _) = listener.accept().unwrap();
+        assert_eq!(stream.read(&mut [0]).unwrap(), 0);
+
+        drop(listener);
+        let err = connector.probe("127.0.0.1", port).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused);
+    }
+
+    #[test]
+    fn connect_with_info() {
+        let refused = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_resolver(Some(Box::new(move |_, _| Ok(vec![refused, addr]))));
+        let (stream, info) = connector.connect_with_info("example.invalid", 80, "http").unwrap();
+        assert_eq!(info.addr(), addr);
+        assert_eq!(info.local_addr(), Some(stream.0.local_addr().unwrap()));
+        assert_eq!(info.index(), 1);
+        assert_eq!(info.addresses(), 2);
+        assert_eq!(info.failed_attempts(), 1);
+        assert_eq!(info.keepalive(), None);
+
+        connector.set_keepalive(Some(Duration::from_secs(3600)));
+        let (_, info) = connector.connect_with_info("example.invalid", 80, "http").unwrap();
+        assert_eq!(info.keepalive(), Some(Duration::from_secs(3600)));
+
+        connector.set_happy_eyeballs(true);
+        let (_, info) = connector.connect_with_info("example.invalid", 80, "http").unwrap();
+        assert_eq!(info.addr(), addr);
+        assert_eq!(info.index(), 1);
+        assert_eq!(info.addresses(), 2);
+    }
+
+    #[test]
+    fn connect_timeout_for_scheme() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_connect_timeout(Some(Duration::from_secs(5)));
+        connector.set_allowed_schemes(vec!["http".to_owned(), "https".to_owned()]);
+        connector.set_connect_timeout_for_scheme("https", Some(Duration::from_secs(0)));
+        assert_eq!(connector.connect_timeout_for_scheme("http"), Some(Duration::from_secs(5)));
+        assert_eq!(connector.connect_timeout_for_scheme("https"), Some(Duration::from_secs(0)));
+
+        connector.connect("127.0.0.1", port, "http").unwrap();
+        match connector.connect("127.0.0.1", port, "https") {
+            Err(hyper::Error::Io(ref e)) if e.kind() == io::ErrorKind::TimedOut => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+
+        connector.set_connect_timeout_for_scheme("https", None);
+        connector.connect("127.0.0.1", port, "https").unwrap();
+    }
+
+    #[test]
+    fn connect_with_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_connect_timeout(Some(Duration::from_secs(0)));
+        assert!(connector.connect("127.0.0.1", port, "http").is_err());
+
+        connector.connect_with_timeout("127.0.0.1", port, "http", Some(Duration::from_secs(5)))
+            .unwrap();
+        connector.connect_with_timeout("127.0.0.1", port, "http", None).unwrap();
+        assert_eq!(connector.connect_timeout(), Some(Duration::from_secs(0)));
+
+        let connector = HttpTimeoutConnector::new();
+        let timeout = Some(Duration::from_secs(0));
+        match connector.connect_with_timeout("127.0.0.1", port, "http", timeout) {
+            Err(hyper::Error::Io(ref e)) => assert_eq!(e.kind(), io::ErrorKind::TimedOut),
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn v6_fallback_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let v6 = SocketAddr::new("::1".parse().unwrap(), addr.port());
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_connect_timeout(Some(Duration::from_secs(5)));
+        connector.set_v6_fallback_timeout(Some(Duration::from_millis(10)));
+        connector.set_resolver(Some(Box::new(move |_, _| Ok(vec![v6, addr]))));
+        let (_, info) = connector.connect_with_info("example.invalid", 80, "http").unwrap();
+        assert_eq!(info.addr(), addr);
+        assert_eq!(info.failed_attempts(), 1);
+    }
+
+    #[test]
+    fn proxy_protocol() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_proxy_protocol(Some(ProxyProtocolVersion::V1));
+        let stream = connector.connect_addr(addr).unwrap();
+        let local = stream.0.local_addr().unwrap();
+
+        let expected = format!("PROXY TCP4 127.0.0.1 127.0.0.1 {} {}\r\n",
+                               local.port(),
+                               addr.port());
+        let mut header = vec![0; expected.len()];
+        listener.accept().unwrap().0.read_exact(&mut header).unwrap();
+        assert_eq!(header, expected.as_bytes());
+    }
+
+    #[test]
+    fn fallback_host() {
+        let refused = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_fallback_host(Some(("127.0.0.1".to_owned(), port)));
+        let stream = connector.connect("127.0.0.1", refused, "http").unwrap();
+        assert_eq!(stream.0.peer_addr().unwrap().port(), port);
+
+        match connector.connect("bad host", refused, "http") {
+            Err(hyper::Error::Io(ref e)) if e.kind() == io::ErrorKind::InvalidInput => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+
+        connector.set_address_filter(Some(Box::new(move |a| a.port() != refused)));
+        match connector.connect("127.0.0.1", refused, "http") {
+            Err(hyper::Error::Io(ref e)) if e.kind() == io::ErrorKind::PermissionDenied => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+        connector.set_address_filter(None);
+
+        drop(listener);
+        match connector.connect("127.0.0.1", refused, "http") {
+            Err(hyper::Error::Io(ref e)) if e.kind() == io::ErrorKind::ConnectionRefused => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn hosts() {
+        let a = TcpListener::bind("127.0.0.1:0").unwrap();
+        let a_port = a.local_addr().unwrap().port();
+        let b = TcpListener::bind("127.0.0.1:0").unwrap();
+        let b_port = b.local_addr().unwrap().port();
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_hosts(vec![("127.0.0.1".to_owned(), a_port),
+                                 ("127.0.0.1".to_owned(), b_port)]);
+        let connector2 = connector.clone();
+
+        let port = |c: &HttpTimeoutConnector| {
+            let stream = c.connect("example.invalid", 80, "http").unwrap();
+            stream.0.peer_addr().unwrap().port()
+        };
+        assert_eq!(port(&connector), a_port);
+        assert_eq!(port(&connector2), b_port);
+        assert_eq!(port(&connector), a_port);
+
+        drop(a);
+        assert_eq!(port(&connector), b_port);
+        assert_eq!(port(&connector), b_port);
+
+        drop(b);
+        match connector.connect("example.invalid", 80, "http") {
+            Err(hyper::Error::Io(ref e)) if e.kind() == io::ErrorKind::ConnectionRefused => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn schemes() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let mut connector = HttpTimeoutConnector::new();
+        assert!(connector.connect("127.0.0.1", port, "custom").is_err());
+
+        connector.set_allowed_schemes(vec!["http".to_owned(), "custom".to_owned()]);
+        connector.connect("127.0.0.1", port, "custom").unwrap();
+        assert!(connector.connect("127.0.0.1", port, "other").is_err());
+
+        connector.set_strict_scheme(false);
+        connector.connect("127.0.0.1", port, "other").unwrap();
+    }
+
     #[test]
     fn ok() {
         let mut connector = HttpTimeoutConnector::new();
@@ -0,0 +1,74 @@
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Instant;
+
+/// The number of connect calls in progress, shared between clones of a
+/// connector.
+pub struct InFlight {
+    count: Mutex<usize>,
+    cond: Condvar,
+}
+
+impl InFlight {
+    pub fn new() -> InFlight {
+        InFlight {
+            count: Mutex::new(0),
+            cond: Condvar::new(),
+        }
+    }
+}
+
+/// A connect call counted towards the limit, which is released on drop.
+pub struct Permit(Arc<InFlight>);
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        *self.0.count.lock().unwrap() -= 1;
+        self.0.cond.notify_one();
+    }
+}
+
+/// Waits until fewer than `limit` connect calls are in progress, returning
+/// `None` if the deadline passes first.
+pub fn acquire(in_flight: &Arc<InFlight>,
+               limit: usize,
+               deadline: Option<Instant>)
+               -> Option<Permit> {
+    let mut count = in_flight.count.lock().unwrap();
+    while *count >= limit {
+        count = match deadline {
+            Some(deadline) => {
+                let now = Instant::now();
+                if now >= deadline {
+                    return None;
+                }
+                in_flight.cond.wait_timeout(count, deadline - now).unwrap().0
+            }
+            None => in_flight.cond.wait(count).unwrap(),
+        };
+    }
+
+    *count += 1;
+    Some(Permit(in_flight.clone()))
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn limit() {
+        let in_flight = Arc::new(InFlight::new());
+        let a = acquire(&in_flight, 2, None).unwrap();
+        let _b = acquire(&in_flight, 2, None).unwrap();
+        assert!(acquire(&in_flight, 2, Some(Instant::now())).is_none());
+
+        let deadline = Instant::now() + Duration::from_millis(10);
+        assert!(acquire(&in_flight, 2, Some(deadline)).is_none());
+        assert!(Instant::now() >= deadline);
+
+        drop(a);
+        acquire(&in_flight, 2, Some(Instant::now())).unwrap();
+    }
+}
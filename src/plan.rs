@@ -0,0 +1,170 @@
+use std::io;
+use std::net::SocketAddr;
+
+use {HttpTimeoutConnector, happy_eyeballs};
+
+/// The addresses a connect call to a host would try, as computed by
+/// `HttpTimeoutConnector::connection_plan`.
+#[derive(Clone, Debug)]
+pub struct ConnectionPlan {
+    host: String,
+    port: u16,
+    addresses: Vec<PlannedAddress>,
+    resolved_from_cache: bool,
+    happy_eyeballs: bool,
+}
+
+impl ConnectionPlan {
+    /// Returns the host whose addresses would be tried.
+    ///
+    /// When connecting through a proxy, this is the proxy's host.
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// Returns the port which would be connected to.
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Returns the addresses which would be tried, in the order their
+    /// attempts would be started.
+    pub fn addresses(&self) -> &[PlannedAddress] {
+        &self.addresses
+    }
+
+    /// Returns whether the addresses of the host were taken from the DNS
+    /// cache rather than resolved.
+    pub fn resolved_from_cache(&self) -> bool {
+        self.resolved_from_cache
+    }
+
+    /// Returns whether the addresses would be raced using Happy Eyeballs
+    /// rather than tried one after another.
+    pub fn happy_eyeballs(&self) -> bool {
+        self.happy_eyeballs
+    }
+}
+
+/// An address in a `ConnectionPlan`.
+#[derive(Clone, Debug)]
+pub struct PlannedAddress {
+    addr: SocketAddr,
+    sticky: bool,
+    cooling_down: bool,
+}
+
+impl PlannedAddress {
+    /// Returns the address.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Returns whether this is an IPv6 address.
+    pub fn is_ipv6(&self) -> bool {
+        self.addr.is_ipv6()
+    }
+
+    /// Returns whether the address was moved to the front because the last
+    /// connect call to the host connected to it.
+    pub fn sticky(&self) -> bool {
+        self.sticky
+    }
+
+    /// Returns whether the address was moved towards the end because a
+    /// connection attempt to it recently failed.
+    pub fn cooling_down(&self) -> bool {
+        self.cooling_down
+    }
+}
+
+impl HttpTimeoutConnector {
+    /// Returns the addresses a connect call to a host would try, without
+    /// making any connection attempts.
+    ///
+    /// This performs the same resolution and ordering as a connect call, and
+    /// reports why addresses were reordered by the sticky address and address
+    /// cooldown options. Addresses rejected by the address filter are left
+    /// out. When connecting through a proxy, the plan is for the connection
+    /// to the proxy. Configured hosts and the fallback host are not taken
+    /// into account.
+    ///
+    /// Like `resolve`, this never creates a socket.
+    pub fn connection_plan(&self, host: &str, port: u16) -> io::Result<ConnectionPlan> {
+        let (host, port) = match self.proxy_for(host) {
+            Some(proxy) => (proxy.host(), proxy.port()),
+            None => (host, port),
+        };
+
+        let (addrs, cached) = self.resolve_until(host, port, self.deadline())?;
+        let mut addrs = self.filter_addrs(host, addrs)?;
+        if self.happy_eyeballs {
+            addrs = happy_eyeballs::interleave(addrs);
+        }
+
+        let sticky = if self.sticky_addresses {
+            self.sticky_addrs.get(host, port)
+        } else {
+            None
+        };
+        let addresses = addrs.into_iter()
+            .map(|addr| {
+                PlannedAddress {
+                    addr,
+                    sticky: sticky == Some(addr),
+                    cooling_down: self.address_cooldown.is_some() &&
+                                  self.failed_addrs.is_cooling_down(&addr),
+                }
+            })
+            .collect();
+
+        Ok(ConnectionPlan {
+            host: host.to_owned(),
+            port,
+            addresses,
+            resolved_from_cache: cached,
+            happy_eyeballs: self.happy_eyeballs,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use hyper::net::NetworkConnector;
+    use std::net::TcpListener;
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn plan() {
+        let refused = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let v6: SocketAddr = "[::1]:80".parse().unwrap();
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_address_cooldown(Some(Duration::from_secs(60)));
+        connector.set_sticky_addresses(true);
+        connector.set_resolver(Some(Box::new(move |_, _| Ok(vec![refused, addr, v6]))));
+
+        let plan = connector.connection_plan("example.invalid", 80).unwrap();
+        assert_eq!(plan.host(), "example.invalid");
+        assert_eq!(plan.port(), 80);
+        assert!(!plan.happy_eyeballs());
+        let addrs = plan.addresses().iter().map(|a| a.addr()).collect::<Vec<_>>();
+        assert_eq!(addrs, vec![refused, addr, v6]);
+        assert!(plan.addresses().iter().all(|a| !a.sticky() && !a.cooling_down()));
+        assert!(plan.addresses()[2].is_ipv6());
+
+        connector.connect_addrs(&[refused]).unwrap_err();
+        connector.connect("example.invalid", 80, "http").unwrap();
+        connector.set_happy_eyeballs(true);
+        let plan = connector.connection_plan("example.invalid", 80).unwrap();
+        assert!(plan.happy_eyeballs());
+        let addrs = plan.addresses().iter().map(|a| a.addr()).collect::<Vec<_>>();
+        assert_eq!(addrs, vec![addr, v6, refused]);
+        assert!(plan.addresses()[0].sticky());
+        assert!(plan.addresses()[2].cooling_down());
+    }
+}
@@ -0,0 +1,654 @@
+use std::cmp;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, TcpStream};
+use std::time::{Duration, Instant};
+
+use {ConnectError, Connected, HttpTimeoutConnector};
+
+/// The maximum size of a proxy's response to a `CONNECT` request.
+const MAX_RESPONSE_SIZE: usize = 8 * 1024;
+
+#[derive(Clone, Debug)]
+enum Protocol {
+    Http,
+    Socks5,
+}
+
+/// The configuration of a proxy through which connections are tunneled.
+#[derive(Clone)]
+pub struct ProxyConfig {
+    protocol: Protocol,
+    host: String,
+    port: u16,
+    handshake_timeout: Option<Duration>,
+    credentials: Option<(String, String)>,
+}
+
+impl fmt::Debug for ProxyConfig {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("ProxyConfig")
+            .field("protocol", &self.protocol)
+            .field("host", &self.host)
+            .field("port", &self.port)
+            .field("handshake_timeout", &self.handshake_timeout)
+            .field("credentials", &self.credentials.as_ref().map(|(u, _)| (u, "..")))
+            .finish()
+    }
+}
+
+impl ProxyConfig {
+    /// Creates a configuration for an HTTP proxy which supports the `CONNECT`
+    /// method.
+    ///
+    /// The configuration initially has no handshake timeout.
+    pub fn http(host: &str, port: u16) -> ProxyConfig {
+        ProxyConfig::new(Protocol::Http, host, port)
+    }
+
+    /// Creates a configuration for a SOCKS5 proxy.
+    ///
+    /// The configuration initially has no handshake timeout or credentials.
+    pub fn socks5(host: &str, port: u16) -> ProxyConfig {
+        ProxyConfig::new(Protocol::Socks5, host, port)
+    }
+
+    fn new(protocol: Protocol, host: &str, port: u16) -> ProxyConfig {
+        ProxyConfig {
+            protocol,
+            host: host.to_owned(),
+            port,
+            handshake_timeout: None,
+            credentials: None,
+        }
+    }
+
+    /// Returns the host of the proxy.
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// Returns the port of the proxy.
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Returns the handshake timeout.
+    pub fn handshake_timeout(&self) -> Option<Duration> {
+        self.handshake_timeout
+    }
+
+    /// Sets the handshake timeout.
+    ///
+    /// This bounds the time taken to establish the tunnel once the connection
+    /// to the proxy itself has been made. The tunnel is also bounded by the
+    /// connector's connection timeout, measured from the start of the attempt
+    /// to connect to the proxy, and by its total timeout, so those bound the
+    /// whole of a proxied connect even without a handshake timeout. If one of
+    /// them elapses first, the connect call fails with a
+    /// `ConnectError::Timeout` or `ConnectError::DeadlineExceeded` error
+    /// rather than a `ConnectError::Proxy` error.
+    pub fn set_handshake_timeout(&mut self, timeout: Option<Duration>) {
+        self.handshake_timeout = timeout;
+    }
+
+    /// Returns the username and password used to authenticate with the proxy.
+    pub fn credentials(&self) -> Option<(&str, &str)> {
+        self.credentials.as_ref().map(|(u, p)| (&**u, &**p))
+    }
+
+    /// Sets the username and password used to authenticate with the proxy.
+    ///
+    /// Credentials are only supported by SOCKS5 proxies, and are ignored for
+    /// HTTP proxies. If set, the proxy may choose between username/password
+    /// authentication and no authentication.
+    pub fn set_credentials(&mut self, credentials: Option<(String, String)>) {
+        self.credentials = credentials;
+    }
+}
+
+/// Determines if connections to a host bypass the proxy.
+///
+/// Each pattern is either `*`, which matches every host, an IP address or
+/// CIDR block such as `10.0.0.0/8`, which matches IP address hosts within it,
+/// or a domain, which matches that domain and its subdomains.
+pub fn bypassed(bypass: &[String], host: &str) -> bool {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let ip = host.parse::<IpAddr>().ok();
+    bypass.iter().any(|pattern| {
+        let pattern = pattern.trim();
+        if pattern == "*" {
+            return true;
+        }
+        if let Some(ip) = ip {
+            return matches_cidr(pattern, ip);
+        }
+
+        let domain = pattern.trim_start_matches('*').trim_start_matches('.').to_ascii_lowercase();
+        if domain.is_empty() {
+            return false;
+        }
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        host == domain || host.ends_with(&format!(".{}", domain))
+    })
+}
+
+fn matches_cidr(pattern: &str, ip: IpAddr) -> bool {
+    let (network, len) = match pattern.find('/') {
+        Some(i) => {
+            match pattern[i + 1..].parse::<u32>() {
+                Ok(len) => (&pattern[..i], Some(len)),
+                Err(_) => return false,
+            }
+        }
+        None => (pattern, None),
+    };
+    let network = match network.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+        Ok(network) => network,
+        Err(_) => return false,
+    };
+
+    let (network, ip, bits) = match (network, ip) {
+        (IpAddr::V4(network), IpAddr::V4(ip)) => {
+            (u128::from(u32::from(network)), u128::from(u32::from(ip)), 32)
+        }
+        (IpAddr::V6(network), IpAddr::V6(ip)) => (u128::from(network), u128::from(ip), 128),
+        _ => return false,
+    };
+    let len = len.unwrap_or(bits);
+    len <= bits && prefix(network, len, bits) == prefix(ip, len, bits)
+}
+
+/// Returns the first `len` bits of a `bits`-bit value.
+fn prefix(value: u128, len: u32, bits: u32) -> u128 {
+    if len == 0 {
+        0
+    } else {
+        value >> (bits - len)
+    }
+}
+
+/// Connects to the target host through the proxy.
+pub fn connect(connector: &HttpTimeoutConnector,
+               proxy: &ProxyConfig,
+               host: &str,
+               port: u16,
+               timeout: Option<Duration>,
+               deadline: Option<Instant>)
+               -> Result<Connected, ConnectError> {
+    let Connected { mut stream, mut info } =
+        connector.connect_direct(&proxy.host, proxy.port, timeout, deadline)?;
+
+    let start = Instant::now();
+    // the connection timeout of the attempt to reach the proxy and the total
+    // timeout also cover establishing the tunnel
+    let attempt_start = start.checked_sub(info.elapsed).unwrap_or(start);
+    let connect_deadline = earliest(timeout.map(|t| attempt_start + t), deadline);
+    let handshake_deadline = proxy.handshake_timeout.map(|t| start + t);
+    let tunnel_deadline = earliest(connect_deadline, handshake_deadline);
+    let r = match proxy.protocol {
+        Protocol::Http => http_connect(&mut stream, host, port, tunnel_deadline),
+        Protocol::Socks5 => socks5_connect(&mut stream, proxy, host, port, tunnel_deadline),
+    };
+    // whether a timeout was due to the connector's timeouts rather than the
+    // proxy's handshake timeout
+    let connect_bound = connect_deadline.is_some() && connect_deadline == tunnel_deadline &&
+                        connect_deadline != handshake_deadline;
+    match r {
+        Ok(()) => {}
+        Err(ref e) if e.kind() == io::ErrorKind::TimedOut && connect_bound => {
+            let err = io::Error::new(io::ErrorKind::TimedOut,
+                                     "connection through proxy timed out during handshake");
+            return Err(ConnectError::Timeout(err));
+        }
+        Err(e) => return Err(ConnectError::Proxy(e)),
+    }
+
+    connector.set_stream_timeouts(&stream).map_err(ConnectError::Other)?;
+    info.elapsed += start.elapsed();
+    info.timing.connect += start.elapsed();
+    Ok(Connected { stream, info })
+}
+
+fn http_connect(stream: &mut TcpStream,
+                host: &str,
+                port: u16,
+                deadline: Option<Instant>)
+                -> io::Result<()> {
+    // hyper passes IPv6 literals already bracketed
+    let authority = if host.contains(':') && !host.starts_with('[') {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    };
+    let request = format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n\r\n", authority);
+
+    write_all(stream, request.as_bytes(), deadline)?;
+
+    // read a byte at a time to avoid consuming any data past the end of the
+    // response
+    let mut response = vec![];
+    while !response.ends_with(b"\r\n\r\n") {
+        if response.len() >= MAX_RESPONSE_SIZE {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                      "proxy response too large"));
+        }
+
+        let mut byte = [0];
+        read_exact(stream, &mut byte, deadline)?;
+        response.push(byte[0]);
+    }
+
+    let response = String::from_utf8_lossy(&response);
+    let status_line = response.lines().next().unwrap_or("");
+    let mut parts = status_line.split(' ');
+    match (parts.next(), parts.next()) {
+        (Some(version), Some(code)) if version.starts_with("HTTP/1.") && code.len() == 3 &&
+                                       code.starts_with('2') => Ok(()),
+        _ => {
+            Err(io::Error::new(io::ErrorKind::Other,
+                               format!("proxy returned an error response: {}", status_line)))
+        }
+    }
+}
+
+fn socks5_connect(stream: &mut TcpStream,
+                  proxy: &ProxyConfig,
+                  host: &str,
+                  port: u16,
+                  deadline: Option<Instant>)
+                  -> io::Result<()> {
+    // method negotiation
+    let request: &[u8] = if proxy.credentials.is_some() {
+        &[5, 2, 0, 2]
+    } else {
+        &[5, 1, 0]
+    };
+    write_all(stream, request, deadline)?;
+
+    let mut response = [0; 2];
+    read_exact(stream, &mut response, deadline)?;
+    if response[0] != 5 {
+        return Err(invalid_data("invalid SOCKS5 response version"));
+    }
+    match (response[1], &proxy.credentials) {
+        (0, _) => {}
+        (2, &Some((ref username, ref password))) => {
+            socks5_authenticate(stream, username, password, deadline)?
+        }
+        (0xff, _) => {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied,
+                                      "no acceptable SOCKS5 authentication methods"))
+        }
+        _ => return Err(invalid_data("invalid SOCKS5 authentication method")),
+    }
+
+    // connect request
+    let mut request = vec![5, 1, 0];
+    match host.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => {
+            request.push(1);
+            request.extend_from_slice(&ip.octets());
+        }
+        Ok(IpAddr::V6(ip)) => {
+            request.push(4);
+            request.extend_from_slice(&ip.octets());
+        }
+        Err(_) => {
+            if host.len() > 255 {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                          "host too long for SOCKS5"));
+            }
+            request.push(3);
+            request.push(host.len() as u8);
+            request.extend_from_slice(host.as_bytes());
+        }
+    }
+    request.push((port >> 8) as u8);
+    request.push(port as u8);
+    write_all(stream, &request, deadline)?;
+
+    let mut response = [0; 4];
+    read_exact(stream, &mut response, deadline)?;
+    if response[0] != 5 {
+        return Err(invalid_data("invalid SOCKS5 response version"));
+    }
+    if response[1] != 0 {
+        let message = match response[1] {
+            1 => "general SOCKS server failure",
+            2 => "connection not allowed by ruleset",
+            3 => "network unreachable",
+            4 => "host unreachable",
+            5 => "connection refused",
+            6 => "TTL expired",
+            7 => "command not supported",
+            8 => "address type not supported",
+            _ => "unknown error",
+        };
+        return Err(io::Error::new(io::ErrorKind::Other,
+                                  format!("SOCKS5 proxy returned an error: {}", message)));
+    }
+
+    // the bound address, which we don't care about
+    let len = match response[3] {
+        1 => 4,
+        4 => 16,
+        3 => {
+            let mut len = [0];
+            read_exact(stream, &mut len, deadline)?;
+            len[0] as usize
+        }
+        _ => return Err(invalid_data("invalid SOCKS5 address type")),
+    };
+    let mut addr = vec![0; len + 2];
+    read_exact(stream, &mut addr, deadline)
+}
+
+fn socks5_authenticate(stream: &mut TcpStream,
+                       username: &str,
+                       password: &str,
+                       deadline: Option<Instant>)
+                       -> io::Result<()> {
+    if username.len() > 255 || password.len() > 255 {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                  "SOCKS5 username and password must be at most 255 bytes"));
+    }
+
+    let mut request = vec![1, username.len() as u8];
+    request.extend_from_slice(username.as_bytes());
+    request.push(password.len() as u8);
+    request.extend_from_slice(password.as_bytes());
+    write_all(stream, &request, deadline)?;
+
+    let mut response = [0; 2];
+    read_exact(stream, &mut response, deadline)?;
+    if response[1] != 0 {
+        return Err(io::Error::new(io::ErrorKind::PermissionDenied,
+                                  "SOCKS5 authentication failed"));
+    }
+
+    Ok(())
+}
+
+fn write_all(stream: &mut TcpStream, buf: &[u8], deadline: Option<Instant>) -> io::Result<()> {
+    set_timeouts(stream, deadline)?;
+    stream.write_all(buf).map_err(timed_out)
+}
+
+fn read_exact(stream: &mut TcpStream, mut buf: &mut [u8], deadline: Option<Instant>) -> io::Result<()> {
+    while !buf.is_empty() {
+        set_timeouts(stream, deadline)?;
+        match stream.read(buf).map_err(timed_out)? {
+            0 => {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof,
+                                          "proxy closed the connection"))
+            }
+            n => buf = &mut buf[n..],
+        }
+    }
+
+    Ok(())
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Returns the earlier of two deadlines.
+fn earliest(a: Option<Instant>, b: Option<Instant>) -> Option<Instant> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(cmp::min(a, b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// Bounds the stream's reads and writes by the deadline.
+fn set_timeouts(stream: &TcpStream, deadline: Option<Instant>) -> io::Result<()> {
+    let deadline = match deadline {
+        Some(deadline) => deadline,
+        None => return Ok(()),
+    };
+
+    let now = Instant::now();
+    if now >= deadline {
+        return Err(handshake_timed_out());
+    }
+
+    stream.set_read_timeout(Some(deadline - now))?;
+    stream.set_write_timeout(Some(deadline - now))
+}
+
+/// Normalizes the errors produced when a socket timeout elapses.
+fn timed_out(err: io::Error) -> io::Error {
+    match err.kind() {
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => handshake_timed_out(),
+        _ => err,
+    }
+}
+
+fn handshake_timed_out() -> io::Error {
+    io::Error::new(io::ErrorKind::TimedOut, "proxy handshake timed out")
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::TcpListener;
+    use std::thread;
+
+    use super::*;
+
+    fn proxy(response: &'static str) -> (u16, thread::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let t = thread::spawn(move || {
+            let mut stream = listener.accept().unwrap().0;
+            let mut request = vec![];
+            while !request.ends_with(b"\r\n\r\n") {
+                let mut byte = [0];
+                stream.read_exact(&mut byte).unwrap();
+                request.push(byte[0]);
+            }
+            stream.write_all(response.as_bytes()).unwrap();
+            String::from_utf8(request).unwrap()
+        });
+        (port, t)
+    }
+
+    #[test]
+    fn bypass() {
+        let bypass = vec!["localhost".to_owned(),
+                          ".internal.example".to_owned(),
+                          "10.0.0.0/8".to_owned(),
+                          "::1".to_owned()];
+        assert!(bypassed(&bypass, "localhost"));
+        assert!(bypassed(&bypass, "LOCALHOST."));
+        assert!(!bypassed(&bypass, "notlocalhost"));
+        assert!(bypassed(&bypass, "internal.example"));
+        assert!(bypassed(&bypass, "api.internal.example"));
+        assert!(!bypassed(&bypass, "example"));
+        assert!(bypassed(&bypass, "10.1.2.3"));
+        assert!(!bypassed(&bypass, "11.1.2.3"));
+        assert!(bypassed(&bypass, "[::1]"));
+        assert!(!bypassed(&bypass, "::2"));
+        assert!(!bypassed(&bypass, "example.com"));
+
+        assert!(bypassed(&["*".to_owned()], "example.com"));
+        assert!(bypassed(&["0.0.0.0/0".to_owned()], "192.168.0.1"));
+        assert!(bypassed(&["2001:db8::/32".to_owned()], "2001:db8::1"));
+        assert!(!bypassed(&["10.0.0.0/33".to_owned()], "10.0.0.1"));
+    }
+
+    #[test]
+    fn bypass_connect() {
+        let (port, t) = proxy("HTTP/1.1 200 Connection established\r\n\r\n");
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let target = listener.local_addr().unwrap().port();
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_proxy(Some(ProxyConfig::http("127.0.0.1", port)));
+        connector.set_proxy_bypass(vec!["127.0.0.0/8".to_owned()]);
+        let stream = connector.connect_host("127.0.0.1", target, None, None).unwrap().stream;
+        assert_eq!(stream.peer_addr().unwrap().port(), target);
+
+        connector.set_proxy_bypass(vec!["example.com".to_owned()]);
+        let stream = connector.connect_host("127.0.0.1", target, None, None).unwrap().stream;
+        assert_eq!(stream.peer_addr().unwrap().port(), port);
+        assert!(t.join().unwrap().starts_with(&format!("CONNECT 127.0.0.1:{} ", target)));
+    }
+
+    #[test]
+    fn http_connect() {
+        let (port, t) = proxy("HTTP/1.1 200 Connection established\r\n\r\nhello");
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_proxy(Some(ProxyConfig::http("127.0.0.1", port)));
+        let mut stream = connector.connect_host("example.invalid", 80, None, None).unwrap().stream;
+
+        let mut buf = [0; 5];
+        stream.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"hello");
+        assert_eq!(t.join().unwrap(),
+                   "CONNECT example.invalid:80 HTTP/1.1\r\nHost: example.invalid:80\r\n\r\n");
+    }
+
+    #[test]
+    fn http_connect_ipv6() {
+        for host in &["::1", "[::1]"] {
+            let (port, t) = proxy("HTTP/1.1 200 Connection established\r\n\r\n");
+
+            let mut connector = HttpTimeoutConnector::new();
+            connector.set_proxy(Some(ProxyConfig::http("127.0.0.1", port)));
+            connector.connect_host(host, 80, None, None).unwrap();
+            assert_eq!(t.join().unwrap(),
+                       "CONNECT [::1]:80 HTTP/1.1\r\nHost: [::1]:80\r\n\r\n");
+        }
+    }
+
+    #[test]
+    fn http_connect_error() {
+        let (port, t) = proxy("HTTP/1.1 407 Proxy Authentication Required\r\n\r\n");
+
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_proxy(Some(ProxyConfig::http("127.0.0.1", port)));
+        match connector.connect_host("example.invalid", 80, None, None) {
+            Err(ConnectError::Proxy(_)) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+        t.join().unwrap();
+    }
+
+    fn socks5_proxy(auth: bool) -> (u16, thread::JoinHandle<Vec<u8>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let t = thread::spawn(move || {
+            let mut stream = listener.accept().unwrap().0;
+            let mut buf = [0; 2];
+            stream.read_exact(&mut buf).unwrap();
+            let mut methods = vec![0; buf[1] as usize];
+            stream.read_exact(&mut methods).unwrap();
+
+            if auth {
+                assert!(methods.contains(&2));
+                stream.write_all(&[5, 2]).unwrap();
+                let mut buf = [0; 2];
+                stream.read_exact(&mut buf).unwrap();
+                let mut username = vec![0; buf[1] as usize];
+                stream.read_exact(&mut username).unwrap();
+                let mut len = [0];
+                stream.read_exact(&mut len).unwrap();
+                let mut password = vec![0; len[0] as usize];
+                stream.read_exact(&mut password).unwrap();
+                assert_eq!(username, b"user");
+                assert_eq!(password, b"pass");
+                stream.write_all(&[1, 0]).unwrap();
+            } else {
+                stream.write_all(&[5, 0]).unwrap();
+            }
+
+            let mut request = vec![0; 5];
+            stream.read_exact(&mut request).unwrap();
+            let mut rest = vec![0; request[4] as usize + 2];
+            stream.read_exact(&mut rest).unwrap();
+            request.extend(rest);
+
+            stream.write_all(&[5, 0, 0, 1, 127, 0, 0, 1, 0, 80]).unwrap();
+            stream.write_all(b"hello").unwrap();
+            request
+        });
+        (port, t)
+    }
+
+    #[test]
+    fn socks5() {
+        for &auth in &[false, true] {
+            let (port, t) = socks5_proxy(auth);
+
+            let mut proxy = ProxyConfig::socks5("127.0.0.1", port);
+            if auth {
+                proxy.set_credentials(Some(("user".to_owned(), "pass".to_owned())));
+            }
+            let mut connector = HttpTimeoutConnector::new();
+            connector.set_proxy(Some(proxy));
+            let mut stream =
+                connector.connect_host("example.invalid", 80, None, None).unwrap().stream;
+
+            let mut buf = [0; 5];
+            stream.read_exact(&mut buf).unwrap();
+            assert_eq!(&buf, b"hello");
+
+            let mut expected = vec![5, 1, 0, 3, 15];
+            expected.extend_from_slice(b"example.invalid");
+            expected.extend_from_slice(&[0, 80]);
+            assert_eq!(t.join().unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn handshake_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let mut proxy = ProxyConfig::http("127.0.0.1", port);
+        proxy.set_handshake_timeout(Some(Duration::from_millis(100)));
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_proxy(Some(proxy));
+        match connector.connect_host("example.invalid", 80, None, None) {
+            Err(ConnectError::Proxy(ref e)) if e.kind() == io::ErrorKind::TimedOut => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn handshake_shares_deadline() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let mut proxy = ProxyConfig::http("127.0.0.1", port);
+        proxy.set_handshake_timeout(Some(Duration::from_secs(10)));
+        let mut connector = HttpTimeoutConnector::new();
+        connector.set_proxy(Some(proxy));
+        connector.set_connect_timeout(Some(Duration::from_millis(100)));
+        let start = Instant::now();
+        match connector.connect_host("example.invalid", 80, connector.connect_timeout, None) {
+            Err(ConnectError::Timeout(ref e)) if e.kind() == io::ErrorKind::TimedOut => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        connector.set_connect_timeout(None);
+        let deadline = Instant::now() + Duration::from_millis(100);
+        match connector.connect_host("example.invalid", 80, None, Some(deadline)) {
+            Err(ConnectError::DeadlineExceeded(ref e)) if e.kind() == io::ErrorKind::TimedOut => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+        assert!(Instant::now() < deadline + Duration::from_secs(5));
+
+        connector.set_connect_timeout_for_scheme("http", Some(Duration::from_millis(100)));
+        let start = Instant::now();
+        match connector.connect_scheme("example.invalid", 80, "http") {
+            Err(ref e) if e.kind() == io::ErrorKind::TimedOut => {}
+            r => panic!("unexpected result {:?}", r.map(|_| ())),
+        }
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+}
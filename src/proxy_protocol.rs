@@ -0,0 +1,103 @@
+//! The PROXY protocol, as described in
+//! https://www.haproxy.org/download/2.0/doc/proxy-protocol.txt.
+use std::net::SocketAddr;
+
+/// The signature which starts every version 2 header.
+const V2_SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+
+/// A version of the PROXY protocol.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ProxyProtocolVersion {
+    /// The human-readable text format.
+    V1,
+    /// The binary format.
+    V2,
+}
+
+/// Returns the header identifying a connection from `src` to `dst`.
+pub fn header(version: ProxyProtocolVersion, src: SocketAddr, dst: SocketAddr) -> Vec<u8> {
+    match version {
+        ProxyProtocolVersion::V1 => v1_header(src, dst),
+        ProxyProtocolVersion::V2 => v2_header(src, dst),
+    }
+}
+
+fn v1_header(src: SocketAddr, dst: SocketAddr) -> Vec<u8> {
+    let protocol = match (src, dst) {
+        (SocketAddr::V4(_), SocketAddr::V4(_)) => "TCP4",
+        (SocketAddr::V6(_), SocketAddr::V6(_)) => "TCP6",
+        _ => return b"PROXY UNKNOWN\r\n".to_vec(),
+    };
+
+    format!("PROXY {} {} {} {} {}\r\n",
+            protocol,
+            src.ip(),
+            dst.ip(),
+            src.port(),
+            dst.port())
+        .into_bytes()
+}
+
+fn v2_header(src: SocketAddr, dst: SocketAddr) -> Vec<u8> {
+    let mut header = V2_SIGNATURE.to_vec();
+    // version 2, PROXY command
+    header.push(0x21);
+
+    match (src, dst) {
+        (SocketAddr::V4(src), SocketAddr::V4(dst)) => {
+            // TCP over IPv4
+            header.extend_from_slice(&[0x11, 0, 12]);
+            header.extend_from_slice(&src.ip().octets());
+            header.extend_from_slice(&dst.ip().octets());
+        }
+        (SocketAddr::V6(src), SocketAddr::V6(dst)) => {
+            // TCP over IPv6
+            header.extend_from_slice(&[0x21, 0, 36]);
+            header.extend_from_slice(&src.ip().octets());
+            header.extend_from_slice(&dst.ip().octets());
+        }
+        _ => {
+            // unspecified family with no addresses
+            header.extend_from_slice(&[0x00, 0, 0]);
+            return header;
+        }
+    }
+    header.extend_from_slice(&src.port().to_be_bytes());
+    header.extend_from_slice(&dst.port().to_be_bytes());
+
+    header
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn v1() {
+        let src = "127.0.0.1:1234".parse().unwrap();
+        let dst = "10.0.0.1:80".parse().unwrap();
+        assert_eq!(header(ProxyProtocolVersion::V1, src, dst),
+                   b"PROXY TCP4 127.0.0.1 10.0.0.1 1234 80\r\n".to_vec());
+
+        let src = "[::1]:1234".parse().unwrap();
+        let dst = "[2001:db8::1]:443".parse().unwrap();
+        assert_eq!(header(ProxyProtocolVersion::V1, src, dst),
+                   b"PROXY TCP6 ::1 2001:db8::1 1234 443\r\n".to_vec());
+    }
+
+    #[test]
+    fn v2() {
+        let src = "127.0.0.1:1234".parse().unwrap();
+        let dst = "10.0.0.1:80".parse().unwrap();
+        let mut expected = V2_SIGNATURE.to_vec();
+        expected.extend_from_slice(&[0x21, 0x11, 0, 12]);
+        expected.extend_from_slice(&[127, 0, 0, 1, 10, 0, 0, 1, 0x04, 0xd2, 0, 80]);
+        assert_eq!(header(ProxyProtocolVersion::V2, src, dst), expected);
+
+        let src = "[::1]:1234".parse().unwrap();
+        let dst = "[::2]:80".parse().unwrap();
+        let header = header(ProxyProtocolVersion::V2, src, dst);
+        assert_eq!(header.len(), 16 + 36);
+        assert_eq!(&header[12..16], &[0x21, 0x21, 0, 36]);
+    }
+}
@@ -0,0 +1,58 @@
+//! A small, fast, non-cryptographic thread-local random number generator.
+use std::cell::Cell;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+thread_local!(static STATE: Cell<u64> = Cell::new(seed()));
+
+fn seed() -> u64 {
+    // RandomState is randomly keyed per thread, so hashing nothing yields a
+    // random value
+    RandomState::new().build_hasher().finish() | 1
+}
+
+/// Returns a random `u64` using the xorshift64* algorithm.
+pub fn next_u64() -> u64 {
+    STATE.with(|state| {
+        let mut x = state.get();
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        state.set(x);
+        x.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    })
+}
+
+/// Returns a random `f64` in the range `[0, 1)`.
+pub fn next_f64() -> f64 {
+    (next_u64() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Shuffles the slice in place.
+pub fn shuffle<T>(slice: &mut [T]) {
+    for i in (1..slice.len()).rev() {
+        let j = (next_u64() % (i as u64 + 1)) as usize;
+        slice.swap(i, j);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn shuffle_permutes() {
+        let mut values = (0..100).collect::<Vec<_>>();
+        shuffle(&mut values);
+        values.sort();
+        assert_eq!(values, (0..100).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn f64_range() {
+        for _ in 0..1000 {
+            let value = next_f64();
+            assert!((0.0..1.0).contains(&value));
+        }
+    }
+}
@@ -0,0 +1,278 @@
+use std::cmp;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use ConnectError;
+
+/// Counters of connect calls, shared between clones of a connector.
+pub struct Stats {
+    connects: AtomicUsize,
+    succeeded: AtomicUsize,
+    timed_out: AtomicUsize,
+    resolve_failures: AtomicUsize,
+    dns_cache_hits: AtomicUsize,
+    dns_cache_misses: AtomicUsize,
+    latency: Histogram,
+}
+
+impl Stats {
+    pub fn new() -> Stats {
+        Stats {
+            connects: AtomicUsize::new(0),
+            succeeded: AtomicUsize::new(0),
+            timed_out: AtomicUsize::new(0),
+            resolve_failures: AtomicUsize::new(0),
+            dns_cache_hits: AtomicUsize::new(0),
+            dns_cache_misses: AtomicUsize::new(0),
+            latency: Histogram::new(),
+        }
+    }
+
+    pub fn record<T>(&self, r: &Result<T, ConnectError>) {
+        self.connects.fetch_add(1, Ordering::Relaxed);
+        let counter = match *r {
+            Ok(_) => &self.succeeded,
+            Err(ConnectError::Timeout(_)) |
+            Err(ConnectError::DeadlineExceeded(_)) => &self.timed_out,
+            Err(ConnectError::Resolve(_)) => &self.resolve_failures,
+            Err(_) => return,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_dns_cache(&self, hit: bool) {
+        let counter = if hit {
+            &self.dns_cache_hits
+        } else {
+            &self.dns_cache_misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_latency(&self, latency: Duration) {
+        self.latency.record(latency);
+    }
+
+    pub fn latency(&self) -> LatencyStats {
+        self.latency.percentiles()
+    }
+
+    pub fn snapshot(&self) -> ConnectorStats {
+        ConnectorStats {
+            connects: self.connects.load(Ordering::Relaxed),
+            succeeded: self.succeeded.load(Ordering::Relaxed),
+            timed_out: self.timed_out.load(Ordering::Relaxed),
+            resolve_failures: self.resolve_failures.load(Ordering::Relaxed),
+            dns_cache_hits: self.dns_cache_hits.load(Ordering::Relaxed),
+            dns_cache_misses: self.dns_cache_misses.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// A snapshot of the connect calls made by a connector.
+///
+/// The counters are updated independently, so a snapshot taken while connect
+/// calls are in progress may be slightly inconsistent.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ConnectorStats {
+    connects: usize,
+    succeeded: usize,
+    timed_out: usize,
+    resolve_failures: usize,
+    dns_cache_hits: usize,
+    dns_cache_misses: usize,
+}
+
+impl ConnectorStats {
+    /// Returns the number of connect calls made.
+    pub fn connects(&self) -> usize {
+        self.connects
+    }
+
+    /// Returns the number of connect calls which established a connection.
+    pub fn succeeded(&self) -> usize {
+        self.succeeded
+    }
+
+    /// Returns the number of connect calls which failed because a connection
+    /// attempt timed out, or the total timeout elapsed.
+    pub fn timed_out(&self) -> usize {
+        self.timed_out
+    }
+
+    /// Returns the number of connect calls which failed because the hostname
+    /// could not be resolved.
+    pub fn resolve_failures(&self) -> usize {
+        self.resolve_failures
+    }
+
+    /// Returns the number of hostname lookups answered by the DNS cache.
+    ///
+    /// Lookups are only counted if the DNS cache is enabled.
+    pub fn dns_cache_hits(&self) -> usize {
+        self.dns_cache_hits
+    }
+
+    /// Returns the number of hostname lookups which were not in the DNS
+    /// cache, and so were resolved.
+    ///
+    /// Lookups are only counted if the DNS cache is enabled.
+    pub fn dns_cache_misses(&self) -> usize {
+        self.dns_cache_misses
+    }
+}
+
+/// The number of buckets each power of two microseconds is split into.
+const SUB_BUCKETS: u64 = 4;
+
+/// The number of buckets, covering latencies up to a little over an hour.
+const BUCKETS: usize = 128;
+
+/// A histogram of latencies with fixed, logarithmically sized buckets.
+///
+/// Each bucket covers a quarter of a power of two microseconds, so recorded
+/// latencies are accurate to within 25%.
+struct Histogram {
+    buckets: Vec<AtomicUsize>,
+}
+
+impl Histogram {
+    fn new() -> Histogram {
+        Histogram { buckets: (0..BUCKETS).map(|_| AtomicUsize::new(0)).collect() }
+    }
+
+    fn record(&self, latency: Duration) {
+        let micros = latency.as_secs()
+            .saturating_mul(1_000_000)
+            .saturating_add(u64::from(latency.subsec_micros()));
+        let bucket = cmp::min(bucket(micros), BUCKETS - 1);
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn percentiles(&self) -> LatencyStats {
+        let counts = self.buckets.iter().map(|b| b.load(Ordering::Relaxed)).collect::<Vec<_>>();
+        let count = counts.iter().sum();
+        let percentile = |p: usize| {
+            if count == 0 {
+                return Duration::from_secs(0);
+            }
+            // the rank of the sample at the percentile, counting from 1
+            let rank = cmp::max((count * p + 99) / 100, 1);
+            let mut seen = 0;
+            for (i, &n) in counts.iter().enumerate() {
+                seen += n;
+                if seen >= rank {
+                    return Duration::from_micros(upper_bound(i));
+                }
+            }
+            Duration::from_micros(upper_bound(BUCKETS - 1))
+        };
+
+        LatencyStats {
+            count,
+            p50: percentile(50),
+            p90: percentile(90),
+            p99: percentile(99),
+        }
+    }
+}
+
+/// Returns the index of the bucket containing a latency in microseconds.
+fn bucket(micros: u64) -> usize {
+    if micros < SUB_BUCKETS {
+        return micros as usize;
+    }
+
+    let log = 63 - u64::from(micros.leading_zeros());
+    let sub = (micros >> (log - 2)) & (SUB_BUCKETS - 1);
+    ((log - 1) * SUB_BUCKETS + sub) as usize
+}
+
+/// Returns the largest latency in microseconds in a bucket.
+fn upper_bound(bucket: usize) -> u64 {
+    let bucket = bucket as u64;
+    if bucket < SUB_BUCKETS {
+        return bucket;
+    }
+
+    let log = bucket / SUB_BUCKETS + 1;
+    let sub = bucket % SUB_BUCKETS;
+    ((SUB_BUCKETS + sub + 1) << (log - 2)) - 1
+}
+
+/// Percentiles of the time taken by a connector's successful connect calls.
+///
+/// Latencies are tracked in buckets, so each percentile is the upper bound of
+/// the bucket it falls in, and may overestimate the true value by up to 25%.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct LatencyStats {
+    count: usize,
+    p50: Duration,
+    p90: Duration,
+    p99: Duration,
+}
+
+impl LatencyStats {
+    /// Returns the number of successful connect calls recorded.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Returns the median latency.
+    ///
+    /// This is zero if no connect calls have succeeded.
+    pub fn p50(&self) -> Duration {
+        self.p50
+    }
+
+    /// Returns the 90th percentile latency.
+    ///
+    /// This is zero if no connect calls have succeeded.
+    pub fn p90(&self) -> Duration {
+        self.p90
+    }
+
+    /// Returns the 99th percentile latency.
+    ///
+    /// This is zero if no connect calls have succeeded.
+    pub fn p99(&self) -> Duration {
+        self.p99
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn buckets() {
+        for micros in 0..100_000 {
+            let bucket = bucket(micros);
+            assert!(micros <= upper_bound(bucket), "{}", micros);
+            if bucket > 0 {
+                assert!(micros > upper_bound(bucket - 1), "{}", micros);
+            }
+        }
+        assert!(upper_bound(BUCKETS - 1) > 3_600_000_000);
+    }
+
+    #[test]
+    fn percentiles() {
+        let histogram = Histogram::new();
+        assert_eq!(histogram.percentiles(), LatencyStats::default());
+
+        for millis in 1..=100 {
+            histogram.record(Duration::from_millis(millis));
+        }
+        histogram.record(Duration::from_secs(u64::MAX));
+
+        let stats = histogram.percentiles();
+        assert_eq!(stats.count(), 101);
+        assert!(stats.p50() >= Duration::from_millis(51));
+        assert!(stats.p50() <= Duration::from_millis(64));
+        assert!(stats.p90() >= Duration::from_millis(91));
+        assert!(stats.p90() <= Duration::from_millis(114));
+        assert!(stats.p99() >= Duration::from_millis(100));
+        assert!(stats.p99() <= Duration::from_millis(125));
+    }
+}
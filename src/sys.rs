@@ -0,0 +1,339 @@
+//! Socket options which aren't exposed by socket2.
+
+use socket2::Socket;
+use std::io;
+use std::time::Duration;
+
+#[cfg(unix)]
+mod imp {
+    use libc::{self, c_void, socklen_t};
+    use socket2::Socket;
+    use std::io;
+    use std::mem;
+    use std::os::raw::c_int;
+    use std::os::unix::io::AsRawFd;
+
+    pub fn setsockopt<T>(socket: &Socket, level: c_int, name: c_int, value: &T) -> io::Result<()> {
+        setsockopt_raw(socket,
+                       level,
+                       name,
+                       value as *const T as *const c_void,
+                       mem::size_of::<T>())
+    }
+
+    pub fn setsockopt_raw(socket: &Socket,
+                          level: c_int,
+                          name: c_int,
+                          value: *const c_void,
+                          len: usize)
+                          -> io::Result<()> {
+        unsafe {
+            if libc::setsockopt(socket.as_raw_fd(), level, name, value, len as socklen_t) == -1 {
+                Err(io::Error::last_os_error())
+            } else {
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Enables keepalive probes, sent once the connection has been idle for the
+/// given time.
+///
+/// socket2 treats the idle time as milliseconds on Unix, dividing the number
+/// of seconds by 1000, so the options are set directly there. The time is
+/// rounded down to whole seconds, with a minimum of one.
+#[cfg(unix)]
+pub fn set_keepalive(socket: &Socket, idle: Duration) -> io::Result<()> {
+    use libc;
+    use std::cmp;
+    use std::os::raw::c_int;
+
+    imp::setsockopt(socket, libc::SOL_SOCKET, libc::SO_KEEPALIVE, &(1 as c_int))?;
+    let secs = cmp::min(cmp::max(idle.as_secs(), 1), c_int::MAX as u64) as c_int;
+    set_keepalive_idle(socket, secs)
+}
+
+#[cfg(not(unix))]
+pub fn set_keepalive(socket: &Socket, idle: Duration) -> io::Result<()> {
+    socket.set_keepalive(Some(idle))
+}
+
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+fn set_keepalive_idle(socket: &Socket, secs: ::std::os::raw::c_int) -> io::Result<()> {
+    use libc;
+
+    imp::setsockopt(socket, libc::IPPROTO_TCP, libc::TCP_KEEPIDLE, &secs)
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+fn set_keepalive_idle(socket: &Socket, secs: ::std::os::raw::c_int) -> io::Result<()> {
+    use libc;
+
+    imp::setsockopt(socket, libc::IPPROTO_TCP, libc::TCP_KEEPALIVE, &secs)
+}
+
+#[cfg(all(unix,
+          not(any(target_os = "linux",
+                  target_os = "android",
+                  target_os = "freebsd",
+                  target_os = "macos",
+                  target_os = "ios"))))]
+fn set_keepalive_idle(_: &Socket, _: ::std::os::raw::c_int) -> io::Result<()> {
+    Err(unsupported("TCP_KEEPIDLE"))
+}
+
+/// Binds the socket to the named network interface.
+#[cfg(target_os = "linux")]
+pub fn set_bind_device(socket: &Socket, device: &str) -> io::Result<()> {
+    use libc::{self, c_void};
+
+    imp::setsockopt_raw(socket,
+                        libc::SOL_SOCKET,
+                        libc::SO_BINDTODEVICE,
+                        device.as_ptr() as *const c_void,
+                        device.len())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn set_bind_device(_: &Socket, _: &str) -> io::Result<()> {
+    Err(unsupported("SO_BINDTODEVICE"))
+}
+
+/// Sets the hop limit of unicast packets sent over an IPv6 socket.
+#[cfg(unix)]
+pub fn set_unicast_hops_v6(socket: &Socket, hops: u32) -> io::Result<()> {
+    use libc;
+    use std::os::raw::c_int;
+
+    imp::setsockopt(socket,
+                    libc::IPPROTO_IPV6,
+                    libc::IPV6_UNICAST_HOPS,
+                    &(hops as c_int))
+}
+
+#[cfg(not(unix))]
+pub fn set_unicast_hops_v6(_: &Socket, _: u32) -> io::Result<()> {
+    Err(unsupported("IPV6_UNICAST_HOPS"))
+}
+
+/// Sets the type-of-service field of packets sent over an IPv4 socket.
+#[cfg(unix)]
+pub fn set_tos(socket: &Socket, tos: u32) -> io::Result<()> {
+    use libc;
+    use std::os::raw::c_int;
+
+    imp::setsockopt(socket, libc::IPPROTO_IP, libc::IP_TOS, &(tos as c_int))
+}
+
+#[cfg(not(unix))]
+pub fn set_tos(_: &Socket, _: u32) -> io::Result<()> {
+    Err(unsupported("IP_TOS"))
+}
+
+/// Sets the traffic class of packets sent over an IPv6 socket.
+#[cfg(any(target_os = "linux",
+          target_os = "android",
+          target_os = "macos",
+          target_os = "ios",
+          target_os = "freebsd",
+          target_os = "dragonfly",
+          target_os = "netbsd",
+          target_os = "openbsd"))]
+pub fn set_tclass_v6(socket: &Socket, tclass: u32) -> io::Result<()> {
+    use libc;
+    use std::os::raw::c_int;
+
+    imp::setsockopt(socket,
+                    libc::IPPROTO_IPV6,
+                    libc::IPV6_TCLASS,
+                    &(tclass as c_int))
+}
+
+#[cfg(not(any(target_os = "linux",
+              target_os = "android",
+              target_os = "macos",
+              target_os = "ios",
+              target_os = "freebsd",
+              target_os = "dragonfly",
+              target_os = "netbsd",
+              target_os = "openbsd")))]
+pub fn set_tclass_v6(_: &Socket, _: u32) -> io::Result<()> {
+    Err(unsupported("IPV6_TCLASS"))
+}
+
+/// Sets the maximum segment size of the connection made by the socket.
+#[cfg(any(target_os = "linux",
+          target_os = "android",
+          target_os = "macos",
+          target_os = "ios",
+          target_os = "freebsd",
+          target_os = "dragonfly",
+          target_os = "netbsd",
+          target_os = "openbsd"))]
+pub fn set_mss(socket: &Socket, mss: u32) -> io::Result<()> {
+    use libc;
+    use std::os::raw::c_int;
+
+    imp::setsockopt(socket, libc::IPPROTO_TCP, libc::TCP_MAXSEG, &(mss as c_int))
+}
+
+#[cfg(not(any(target_os = "linux",
+              target_os = "android",
+              target_os = "macos",
+              target_os = "ios",
+              target_os = "freebsd",
+              target_os = "dragonfly",
+              target_os = "netbsd",
+              target_os = "openbsd")))]
+pub fn set_mss(_: &Socket, _: u32) -> io::Result<()> {
+    Err(unsupported("TCP_MAXSEG"))
+}
+
+/// Allows multiple sockets to bind to the same address and port.
+#[cfg(any(target_os = "linux",
+          target_os = "android",
+          target_os = "macos",
+          target_os = "ios",
+          target_os = "freebsd",
+          target_os = "dragonfly",
+          target_os = "netbsd",
+          target_os = "openbsd"))]
+pub fn set_reuse_port(socket: &Socket) -> io::Result<()> {
+    use libc;
+    use std::os::raw::c_int;
+
+    imp::setsockopt(socket, libc::SOL_SOCKET, libc::SO_REUSEPORT, &(1 as c_int))
+}
+
+#[cfg(not(any(target_os = "linux",
+              target_os = "android",
+              target_os = "macos",
+              target_os = "ios",
+              target_os = "freebsd",
+              target_os = "dragonfly",
+              target_os = "netbsd",
+              target_os = "openbsd")))]
+pub fn set_reuse_port(_: &Socket) -> io::Result<()> {
+    Err(unsupported("SO_REUSEPORT"))
+}
+
+/// Sets the priority of packets sent over the socket.
+#[cfg(target_os = "linux")]
+pub fn set_priority(socket: &Socket, priority: u32) -> io::Result<()> {
+    use libc;
+    use std::os::raw::c_int;
+
+    imp::setsockopt(socket, libc::SOL_SOCKET, libc::SO_PRIORITY, &(priority as c_int))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn set_priority(_: &Socket, _: u32) -> io::Result<()> {
+    Err(unsupported("SO_PRIORITY"))
+}
+
+/// Sets the time transmitted data may remain unacknowledged before the
+/// connection is closed.
+#[cfg(target_os = "linux")]
+pub fn set_user_timeout(socket: &Socket, timeout: Duration) -> io::Result<()> {
+    use libc;
+    use std::os::raw::c_uint;
+    use std::cmp;
+
+    let millis = timeout.as_secs()
+        .saturating_mul(1_000)
+        .saturating_add(u64::from(timeout.subsec_millis()));
+    let millis = cmp::min(millis, u64::from(c_uint::MAX)) as c_uint;
+    imp::setsockopt(socket, libc::IPPROTO_TCP, libc::TCP_USER_TIMEOUT, &millis)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn set_user_timeout(_: &Socket, _: Duration) -> io::Result<()> {
+    Err(unsupported("TCP_USER_TIMEOUT"))
+}
+
+/// Enables TCP Fast Open for the connection made by the socket.
+#[cfg(target_os = "linux")]
+pub fn set_fastopen_connect(socket: &Socket) -> io::Result<()> {
+    use libc;
+    use std::os::raw::c_int;
+
+    imp::setsockopt(socket, libc::IPPROTO_TCP, libc::TCP_FASTOPEN_CONNECT, &(1 as c_int))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn set_fastopen_connect(_: &Socket) -> io::Result<()> {
+    Err(unsupported("TCP_FASTOPEN_CONNECT"))
+}
+
+/// Sets the congestion control algorithm used by the socket.
+#[cfg(target_os = "linux")]
+pub fn set_congestion_control(socket: &Socket, algorithm: &str) -> io::Result<()> {
+    use libc::{self, c_void};
+
+    let r = imp::setsockopt_raw(socket,
+                                libc::IPPROTO_TCP,
+                                libc::TCP_CONGESTION,
+                                algorithm.as_ptr() as *const c_void,
+                                algorithm.len());
+    match r {
+        Err(ref e) if e.raw_os_error() == Some(libc::ENOENT) => {
+            Err(io::Error::new(io::ErrorKind::InvalidInput,
+                               format!("unknown congestion control algorithm `{}`", algorithm)))
+        }
+        r => r,
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn set_congestion_control(_: &Socket, _: &str) -> io::Result<()> {
+    Err(unsupported("TCP_CONGESTION"))
+}
+
+/// Allows the socket to bind to an address which is not local.
+#[cfg(target_os = "linux")]
+pub fn set_freebind(socket: &Socket) -> io::Result<()> {
+    use libc;
+    use std::os::raw::c_int;
+
+    imp::setsockopt(socket, libc::IPPROTO_IP, libc::IP_FREEBIND, &(1 as c_int))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn set_freebind(_: &Socket) -> io::Result<()> {
+    Err(unsupported("IP_FREEBIND"))
+}
+
+/// Enables transparent proxying on an IPv4 socket.
+#[cfg(target_os = "linux")]
+pub fn set_transparent(socket: &Socket) -> io::Result<()> {
+    use libc;
+    use std::os::raw::c_int;
+
+    imp::setsockopt(socket, libc::IPPROTO_IP, libc::IP_TRANSPARENT, &(1 as c_int))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn set_transparent(_: &Socket) -> io::Result<()> {
+    Err(unsupported("IP_TRANSPARENT"))
+}
+
+/// Enables transparent proxying on an IPv6 socket.
+#[cfg(target_os = "linux")]
+pub fn set_transparent_v6(socket: &Socket) -> io::Result<()> {
+    use libc;
+    use std::os::raw::c_int;
+
+    imp::setsockopt(socket, libc::IPPROTO_IPV6, libc::IPV6_TRANSPARENT, &(1 as c_int))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn set_transparent_v6(_: &Socket) -> io::Result<()> {
+    Err(unsupported("IPV6_TRANSPARENT"))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn unsupported(option: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported,
+                   format!("{} is not supported on this platform", option))
+}
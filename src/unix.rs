@@ -0,0 +1,160 @@
+use hyper;
+use hyper::net::{NetworkConnector, NetworkStream};
+use socket2::{Domain, SockAddr, Socket, Type};
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr};
+use std::os::unix::net;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// A Hyper `NetworkConnector` which connects to a Unix domain socket, offering
+/// a connection timeout.
+///
+/// The host and port of the request are ignored; every connection is made to
+/// the configured socket path.
+///
+/// # Examples
+///
+/// ```no_run
+/// extern crate hyper;
+/// extern crate hyper_timeout_connector;
+///
+/// use hyper::Client;
+/// use hyper_timeout_connector::UnixTimeoutConnector;
+/// use std::time::Duration;
+///
+/// fn main() {
+///     let mut connector = UnixTimeoutConnector::new("/var/run/service.sock");
+///     connector.set_connect_timeout(Some(Duration::from_secs(5)));
+///     let client = Client::with_connector(connector);
+///
+///     let response = client.get("http://localhost/status").send().unwrap();
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct UnixTimeoutConnector {
+    path: PathBuf,
+    connect_timeout: Option<Duration>,
+}
+
+impl UnixTimeoutConnector {
+    /// Creates a new `UnixTimeoutConnector` which connects to the socket at
+    /// the specified path.
+    ///
+    /// The connector initially has no connection timeout.
+    pub fn new<P>(path: P) -> UnixTimeoutConnector
+        where P: AsRef<Path>
+    {
+        UnixTimeoutConnector {
+            path: path.as_ref().to_owned(),
+            connect_timeout: None,
+        }
+    }
+
+    /// Returns the path of the socket connections are made to.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the connection timeout.
+    pub fn connect_timeout(&self) -> Option<Duration> {
+        self.connect_timeout
+    }
+
+    /// Sets the connection timeout.
+    pub fn set_connect_timeout(&mut self, timeout: Option<Duration>) {
+        self.connect_timeout = timeout;
+    }
+
+    fn connect_unix(&self) -> io::Result<net::UnixStream> {
+        let socket = Socket::new(Domain::unix(), Type::stream(), None)?;
+        let addr = SockAddr::unix(&self.path)?;
+        match self.connect_timeout {
+            Some(timeout) => socket.connect_timeout(&addr, timeout)?,
+            None => socket.connect(&addr)?,
+        }
+
+        Ok(socket.into_unix_stream())
+    }
+}
+
+impl NetworkConnector for UnixTimeoutConnector {
+    type Stream = UnixStream;
+
+    fn connect(&self, _: &str, _: u16, scheme: &str) -> hyper::Result<UnixStream> {
+        if scheme != "http" {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid scheme for http")
+                           .into());
+        }
+
+        Ok(UnixStream(self.connect_unix()?))
+    }
+}
+
+/// A Hyper `NetworkStream` over a Unix domain socket.
+#[derive(Debug)]
+pub struct UnixStream(pub net::UnixStream);
+
+impl Read for UnixStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl Write for UnixStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl NetworkStream for UnixStream {
+    fn peer_addr(&mut self) -> io::Result<SocketAddr> {
+        Err(io::Error::new(io::ErrorKind::Unsupported,
+                           "Unix domain sockets do not have an IP address"))
+    }
+
+    fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.0.set_read_timeout(dur)
+    }
+
+    fn set_write_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.0.set_write_timeout(dur)
+    }
+
+    fn close(&mut self, how: Shutdown) -> io::Result<()> {
+        match self.0.shutdown(how) {
+            Ok(_) => Ok(()),
+            // see https://github.com/hyperium/hyper/issues/508
+            Err(ref e) if e.kind() == io::ErrorKind::NotConnected => Ok(()),
+            err => err,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::env;
+    use std::fs;
+    use std::os::unix::net::UnixListener;
+    use std::process;
+
+    use super::*;
+
+    #[test]
+    fn connect() {
+        let path = env::temp_dir().join(format!("hyper-timeout-connector-{}.sock", process::id()));
+        let _ = fs::remove_file(&path);
+        let _listener = UnixListener::bind(&path).unwrap();
+
+        let mut connector = UnixTimeoutConnector::new(&path);
+        connector.set_connect_timeout(Some(Duration::from_secs(5)));
+        connector.connect("localhost", 80, "http").unwrap();
+
+        fs::remove_file(&path).unwrap();
+        assert!(connector.connect("localhost", 80, "http").is_err());
+    }
+}
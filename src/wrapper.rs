@@ -0,0 +1,127 @@
+use hyper;
+use hyper::net::{NetworkConnector, NetworkStream};
+use std::fmt;
+use std::io;
+use std::net::TcpStream;
+use std::sync::Arc;
+
+use HttpTimeoutConnector;
+
+/// A Hyper `NetworkConnector` which wraps the connections made by an
+/// `HttpTimeoutConnector` in a custom stream type.
+///
+/// This is created by `HttpTimeoutConnector::with_stream_wrapper`, and can be
+/// used to instrument every connection, for example by counting the bytes
+/// read and written.
+///
+/// # Examples
+///
+/// ```
+/// extern crate hyper;
+/// extern crate hyper_timeout_connector;
+///
+/// use hyper::Client;
+/// use hyper::net::HttpStream;
+/// use hyper_timeout_connector::HttpTimeoutConnector;
+///
+/// fn main() {
+///     let connector = HttpTimeoutConnector::new().with_stream_wrapper(|stream| {
+///         println!("connected to {}", stream.peer_addr()?);
+///         Ok(HttpStream(stream))
+///     });
+///     let client = Client::with_connector(connector);
+/// }
+/// ```
+pub struct WrappedConnector<S> {
+    connector: HttpTimeoutConnector,
+    wrapper: Arc<dyn Fn(TcpStream) -> io::Result<S> + Send + Sync>,
+}
+
+impl<S> Clone for WrappedConnector<S> {
+    fn clone(&self) -> WrappedConnector<S> {
+        WrappedConnector {
+            connector: self.connector.clone(),
+            wrapper: self.wrapper.clone(),
+        }
+    }
+}
+
+impl<S> fmt::Debug for WrappedConnector<S> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("WrappedConnector")
+            .field("connector", &self.connector)
+            .field("wrapper", &"..")
+            .finish()
+    }
+}
+
+impl<S> WrappedConnector<S> {
+    /// Returns a shared reference to the underlying connector.
+    pub fn connector(&self) -> &HttpTimeoutConnector {
+        &self.connector
+    }
+
+    /// Returns a mutable reference to the underlying connector.
+    pub fn connector_mut(&mut self) -> &mut HttpTimeoutConnector {
+        &mut self.connector
+    }
+}
+
+impl<S> NetworkConnector for WrappedConnector<S>
+    where S: NetworkStream + Send
+{
+    type Stream = S;
+
+    fn connect(&self, host: &str, port: u16, scheme: &str) -> hyper::Result<S> {
+        let connected = self.connector.connect_scheme(host, port, scheme)?;
+        let stream = (self.wrapper)(connected.stream)?;
+        Ok(stream)
+    }
+}
+
+impl HttpTimeoutConnector {
+    /// Converts the connector into one which wraps each connection it makes
+    /// with the provided function.
+    ///
+    /// The function is called with each newly established connection, after
+    /// all of the connector's configuration has been applied. If it returns
+    /// an error, the connect call fails with it.
+    pub fn with_stream_wrapper<F, S>(self, wrapper: F) -> WrappedConnector<S>
+        where F: Fn(TcpStream) -> io::Result<S> + Send + Sync + 'static
+    {
+        WrappedConnector {
+            connector: self,
+            wrapper: Arc::new(wrapper),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use hyper::net::HttpStream;
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[test]
+    fn wrap() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let wrapped = Arc::new(AtomicUsize::new(0));
+
+        let wrapped2 = wrapped.clone();
+        let mut connector = HttpTimeoutConnector::new().with_stream_wrapper(move |stream| {
+            wrapped2.fetch_add(1, Ordering::SeqCst);
+            Ok(HttpStream(stream))
+        });
+        connector.connector_mut().set_nodelay(true);
+
+        let stream = connector.connect("127.0.0.1", port, "http").unwrap();
+        assert!(stream.0.nodelay().unwrap());
+        assert_eq!(wrapped.load(Ordering::SeqCst), 1);
+
+        assert!(connector.connect("127.0.0.1", port, "https").is_err());
+        assert_eq!(wrapped.load(Ordering::SeqCst), 1);
+    }
+}